@@ -1,6 +1,6 @@
 use dotenvy::dotenv;
 use openai::{
-    chat::{ChatCompletion, ChatCompletionMessage, ChatCompletionMessageRole},
+    chat::{ChatCompletion, ChatCompletionMessage, ChatCompletionMessageRole, Content},
     Credentials,
 };
 use std::io::{stdin, stdout, Write};
@@ -13,7 +13,7 @@ async fn main() {
 
     let mut messages = vec![ChatCompletionMessage {
         role: ChatCompletionMessageRole::System,
-        content: Some("You are a large language model built into a command line interface as an example of what the `openai` Rust library made by Valentine Briese can do.".to_string()),
+        content: Some(Content::new_str("You are a large language model built into a command line interface as an example of what the `openai` Rust library made by Valentine Briese can do.")),
         ..Default::default()
     }];
 
@@ -26,7 +26,7 @@ async fn main() {
         stdin().read_line(&mut user_message_content).unwrap();
         messages.push(ChatCompletionMessage {
             role: ChatCompletionMessageRole::User,
-            content: Some(user_message_content),
+            content: Some(Content::new_str(user_message_content)),
             ..Default::default()
         });
 
@@ -35,12 +35,12 @@ async fn main() {
             .create()
             .await
             .unwrap();
-        let returned_message = chat_completion.choices.first().unwrap().message.clone();
+        let returned_message = chat_completion.first_message().unwrap().clone();
 
         println!(
             "{:#?}: {}",
             &returned_message.role,
-            &returned_message.content.clone().unwrap().trim()
+            &returned_message.content.clone().unwrap().text().trim()
         );
 
         messages.push(returned_message);
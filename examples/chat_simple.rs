@@ -1,6 +1,6 @@
 use dotenvy::dotenv;
 use openai::{
-    chat::{ChatCompletion, ChatCompletionMessage, ChatCompletionMessageRole},
+    chat::{ChatCompletion, ChatCompletionMessage, ChatCompletionMessageRole, Content},
     Credentials,
 };
 
@@ -13,12 +13,12 @@ async fn main() {
     let messages = vec![
         ChatCompletionMessage {
             role: ChatCompletionMessageRole::System,
-            content: Some("You are a helpful assistant.".to_string()),
+            content: Some(Content::new_str("You are a helpful assistant.")),
             ..Default::default()
         },
         ChatCompletionMessage {
             role: ChatCompletionMessageRole::User,
-            content: Some("Tell me a random crab fact".to_string()),
+            content: Some(Content::new_str("Tell me a random crab fact")),
             ..Default::default()
         },
     ];
@@ -27,11 +27,11 @@ async fn main() {
         .create()
         .await
         .unwrap();
-    let returned_message = chat_completion.choices.first().unwrap().message.clone();
+    let returned_message = chat_completion.first_message().unwrap().clone();
     // Assistant: Sure! Here's a random crab fact: Crabs communicate with each other by drumming or waving their pincers.
     println!(
         "{:#?}: {}",
         returned_message.role,
-        returned_message.content.unwrap().trim()
+        returned_message.content.unwrap().text().trim()
     );
 }
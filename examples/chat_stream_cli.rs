@@ -1,7 +1,7 @@
 use dotenvy::dotenv;
 use openai::chat::{ChatCompletion, ChatCompletionDelta};
 use openai::{
-    chat::{ChatCompletionMessage, ChatCompletionMessageRole},
+    chat::{ChatCompletionMessage, ChatCompletionMessageRole, Content},
     Credentials,
 };
 use std::io::{stdin, stdout, Write};
@@ -15,7 +15,9 @@ async fn main() {
 
     let mut messages = vec![ChatCompletionMessage {
         role: ChatCompletionMessageRole::System,
-        content: Some("You're an AI that replies to each message verbosely.".to_string()),
+        content: Some(Content::new_str(
+            "You're an AI that replies to each message verbosely.",
+        )),
         ..Default::default()
     }];
 
@@ -28,7 +30,7 @@ async fn main() {
         stdin().read_line(&mut user_message_content).unwrap();
         messages.push(ChatCompletionMessage {
             role: ChatCompletionMessageRole::User,
-            content: Some(user_message_content),
+            content: Some(Content::new_str(user_message_content)),
             ..Default::default()
         });
 
@@ -39,7 +41,7 @@ async fn main() {
             .unwrap();
 
         let chat_completion: ChatCompletion = listen_for_tokens(chat_stream).await;
-        let returned_message = chat_completion.choices.first().unwrap().message.clone();
+        let returned_message = chat_completion.first_message().unwrap().clone();
 
         messages.push(returned_message);
     }
@@ -48,16 +50,18 @@ async fn main() {
 async fn listen_for_tokens(mut chat_stream: Receiver<ChatCompletionDelta>) -> ChatCompletion {
     let mut merged: Option<ChatCompletionDelta> = None;
     while let Some(delta) = chat_stream.recv().await {
-        let choice = &delta.choices[0];
-        if let Some(role) = &choice.delta.role {
-            print!("{:#?}: ", role);
-        }
-        if let Some(content) = &choice.delta.content {
-            print!("{}", content);
-        }
-        if let Some(_) = &choice.finish_reason {
-            // The message being streamed has been fully received.
-            print!("\n");
+        // The final chunk carries only usage, if requested, and has no choices.
+        if let Some(choice) = delta.choices.first() {
+            if let Some(role) = &choice.delta.role {
+                print!("{:#?}: ", role);
+            }
+            if let Some(content) = &choice.delta.content {
+                print!("{}", content);
+            }
+            if let Some(_) = &choice.finish_reason {
+                // The message being streamed has been fully received.
+                print!("\n");
+            }
         }
         stdout().flush().unwrap();
         // Merge completion into accrued.
@@ -0,0 +1,587 @@
+//! Learn how to turn audio into text or text into audio.
+//!
+//! Related guide: [Speech to text](https://platform.openai.com/docs/guides/speech-to-text)
+
+use super::{
+    file_part, openai_post_multipart, openai_request_bytes, openai_request_ok, ApiResponseOrError,
+    Credentials, OpenAiError,
+};
+use derive_builder::Builder;
+use futures_util::StreamExt;
+use reqwest::multipart::Form;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// Generates audio from the input text.
+pub struct Speech;
+
+#[derive(Serialize, Builder, Debug, Clone)]
+#[builder(pattern = "owned")]
+#[builder(name = "SpeechBuilder")]
+#[builder(setter(strip_option, into))]
+#[builder(build_fn(validate = "Self::validate"))]
+pub struct SpeechRequest {
+    /// One of the available TTS models: `tts-1`, `tts-1-hd` or `gpt-4o-mini-tts`.
+    pub model: String,
+    /// The text to generate audio for. The maximum length is 4096 characters.
+    pub input: String,
+    /// The voice to use when generating the audio.
+    /// Supported voices are `alloy`, `ash`, `ballad`, `coral`, `echo`, `fable`, `onyx`, `nova`, `sage`, `shimmer`, and `verse`.
+    pub voice: String,
+    /// Control the voice of the generated audio with additional instructions, such as its tone or accent.
+    /// Only supported by `gpt-4o-mini-tts`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub instructions: Option<String>,
+    /// The format to audio in. Supported formats are `mp3`, `opus`, `aac`, `flac`, `wav`, and `pcm`.
+    /// Defaults to `mp3`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub response_format: Option<String>,
+    /// The speed of the generated audio. Select a value from `0.25` to `4.0`. `1.0` is the default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub speed: Option<f32>,
+    /// The credentials to use for this request.
+    #[serde(skip_serializing)]
+    #[builder(default)]
+    pub credentials: Option<Credentials>,
+}
+
+impl Speech {
+    /// Generates audio from the input text, returning the raw audio file contents.
+    async fn create(request: SpeechRequest) -> ApiResponseOrError<Vec<u8>> {
+        let credentials_opt = request.credentials.clone();
+        let bytes = openai_request_bytes(
+            Method::POST,
+            "audio/speech",
+            |r| r.json(&request),
+            credentials_opt,
+        )
+        .await?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Generates audio from the input text, writing it to `writer` as it arrives
+    /// rather than buffering the whole file. Returns the number of bytes written.
+    async fn stream_to(
+        request: SpeechRequest,
+        mut writer: impl AsyncWrite + Unpin,
+    ) -> ApiResponseOrError<u64> {
+        let credentials_opt = request.credentials.clone();
+        let response = openai_request_ok(
+            Method::POST,
+            "audio/speech",
+            |r| r.json(&request),
+            credentials_opt,
+        )
+        .await?;
+        let mut stream = response.bytes_stream();
+        let mut written = 0;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            writer.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    pub fn builder(
+        model: impl Into<String>,
+        input: impl Into<String>,
+        voice: impl Into<String>,
+    ) -> SpeechBuilder {
+        SpeechBuilder::create_empty()
+            .model(model)
+            .input(input)
+            .voice(voice)
+    }
+}
+
+impl SpeechBuilder {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(Some(speed)) = self.speed {
+            if !(0.25..=4.0).contains(&speed) {
+                return Err(format!("speed must be between 0.25 and 4.0, got {speed}"));
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn create(self) -> ApiResponseOrError<Vec<u8>> {
+        Speech::create(self.build().map_err(OpenAiError::invalid_request)?).await
+    }
+
+    /// Streams the generated audio to `writer`, such as an audio player's input,
+    /// as it arrives. Returns the number of bytes written.
+    pub async fn stream_to(self, writer: impl AsyncWrite + Unpin) -> ApiResponseOrError<u64> {
+        let request = self.build().map_err(OpenAiError::invalid_request)?;
+        Speech::stream_to(request, writer).await
+    }
+}
+
+/// Audio transcribed into the input language.
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Transcription {
+    pub text: String,
+}
+
+/// A transcription with timestamps, returned for the `verbose_json` response format.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct VerboseTranscription {
+    /// The language of the input audio.
+    pub language: String,
+    /// The duration of the input audio, in seconds.
+    pub duration: f64,
+    /// The transcribed text.
+    pub text: String,
+    /// Segments of the transcribed text and their corresponding details.
+    #[serde(default)]
+    pub segments: Vec<TranscriptionSegment>,
+    /// Extracted words and their corresponding timestamps.
+    /// Only present if `word` was requested in the timestamp granularities.
+    #[serde(default)]
+    pub words: Vec<TranscriptionWord>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct TranscriptionSegment {
+    pub id: u32,
+    pub seek: u32,
+    /// Start time of the segment in seconds.
+    pub start: f64,
+    /// End time of the segment in seconds.
+    pub end: f64,
+    pub text: String,
+    pub tokens: Vec<u32>,
+    pub temperature: f64,
+    pub avg_logprob: f64,
+    pub compression_ratio: f64,
+    pub no_speech_prob: f64,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct TranscriptionWord {
+    pub word: String,
+    /// Start time of the word in seconds.
+    pub start: f64,
+    /// End time of the word in seconds.
+    pub end: f64,
+}
+
+/// A transcription, shaped according to the requested response format.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TranscriptionResponse {
+    /// Returned for the `json` response format, which is the default.
+    Json(Transcription),
+    /// Returned for the `verbose_json` response format.
+    VerboseJson(VerboseTranscription),
+    /// The raw response body, returned for the `text`, `srt` and `vtt` response formats.
+    Text(String),
+}
+
+impl TranscriptionResponse {
+    /// The transcribed text. For `srt` and `vtt`, this includes the subtitle markup.
+    pub fn text(&self) -> &str {
+        match self {
+            TranscriptionResponse::Json(transcription) => &transcription.text,
+            TranscriptionResponse::VerboseJson(transcription) => &transcription.text,
+            TranscriptionResponse::Text(text) => text,
+        }
+    }
+
+    /// The language detected in the input audio, such as `english`.
+    /// Only the `verbose_json` response format reports it.
+    pub fn detected_language(&self) -> Option<&str> {
+        match self {
+            TranscriptionResponse::VerboseJson(transcription) => Some(&transcription.language),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TranscriptionResponseFormat {
+    Json,
+    Text,
+    Srt,
+    VerboseJson,
+    Vtt,
+}
+
+impl TranscriptionResponseFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TranscriptionResponseFormat::Json => "json",
+            TranscriptionResponseFormat::Text => "text",
+            TranscriptionResponseFormat::Srt => "srt",
+            TranscriptionResponseFormat::VerboseJson => "verbose_json",
+            TranscriptionResponseFormat::Vtt => "vtt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TimestampGranularity {
+    Word,
+    Segment,
+}
+
+impl TimestampGranularity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TimestampGranularity::Word => "word",
+            TimestampGranularity::Segment => "segment",
+        }
+    }
+}
+
+#[derive(Builder, Debug, Clone)]
+#[builder(pattern = "owned")]
+#[builder(name = "TranscriptionBuilder")]
+#[builder(setter(strip_option, into))]
+pub struct TranscriptionRequest {
+    /// ID of the model to use.
+    /// The options are `gpt-4o-transcribe`, `gpt-4o-mini-transcribe`, and `whisper-1`.
+    pub model: String,
+    /// The local path of the audio file to transcribe, in one of these formats:
+    /// flac, mp3, mp4, mpeg, mpga, m4a, ogg, wav, or webm.
+    pub file: String,
+    /// The format of the transcript output. Defaults to `json`.
+    #[builder(default)]
+    pub response_format: Option<TranscriptionResponseFormat>,
+    /// The language of the input audio, in [ISO-639-1](https://en.wikipedia.org/wiki/List_of_ISO_639-1_codes) format.
+    /// Supplying the input language will improve accuracy and latency.
+    #[builder(default)]
+    pub language: Option<String>,
+    /// An optional text to guide the model's style or continue a previous audio segment.
+    /// The prompt should match the audio language.
+    #[builder(default)]
+    pub prompt: Option<String>,
+    /// The sampling temperature, between 0 and 1.
+    /// Higher values like 0.8 will make the output more random,
+    /// while lower values like 0.2 will make it more focused and deterministic.
+    #[builder(default)]
+    pub temperature: Option<f32>,
+    /// The timestamp granularities to populate for this transcription.
+    /// `response_format` must be set to `verbose_json` to use timestamp granularities.
+    #[builder(default)]
+    pub timestamp_granularities: Vec<TimestampGranularity>,
+    /// The credentials to use for this request.
+    #[builder(default)]
+    pub credentials: Option<Credentials>,
+}
+
+impl TranscriptionRequest {
+    /// The text fields of the multipart form, in the order they are sent.
+    fn text_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![("model", self.model.clone())];
+        if let Some(response_format) = self.response_format {
+            fields.push(("response_format", response_format.as_str().to_string()));
+        }
+        if let Some(language) = &self.language {
+            fields.push(("language", language.clone()));
+        }
+        if let Some(prompt) = &self.prompt {
+            fields.push(("prompt", prompt.clone()));
+        }
+        if let Some(temperature) = self.temperature {
+            fields.push(("temperature", temperature.to_string()));
+        }
+        for granularity in &self.timestamp_granularities {
+            fields.push((
+                "timestamp_granularities[]",
+                granularity.as_str().to_string(),
+            ));
+        }
+        fields
+    }
+}
+
+impl Transcription {
+    async fn create(request: TranscriptionRequest) -> ApiResponseOrError<TranscriptionResponse> {
+        let mut form = Form::new();
+        for (name, value) in request.text_fields() {
+            form = form.text(name, value);
+        }
+        form = form.part("file", audio_file_part(&request.file).await?);
+        let route = "audio/transcriptions";
+        let credentials_opt = request.credentials;
+        match request.response_format {
+            None | Some(TranscriptionResponseFormat::Json) => {
+                openai_post_multipart(route, form, credentials_opt)
+                    .await
+                    .map(TranscriptionResponse::Json)
+            }
+            Some(TranscriptionResponseFormat::VerboseJson) => {
+                openai_post_multipart(route, form, credentials_opt)
+                    .await
+                    .map(TranscriptionResponse::VerboseJson)
+            }
+            Some(_) => {
+                let bytes = openai_request_bytes(
+                    Method::POST,
+                    route,
+                    |r| r.multipart(form),
+                    credentials_opt,
+                )
+                .await?;
+                Ok(TranscriptionResponse::Text(
+                    String::from_utf8_lossy(&bytes).into_owned(),
+                ))
+            }
+        }
+    }
+
+    pub fn builder(model: impl Into<String>, file: impl Into<String>) -> TranscriptionBuilder {
+        TranscriptionBuilder::create_empty().model(model).file(file)
+    }
+}
+
+impl TranscriptionBuilder {
+    pub async fn create(self) -> ApiResponseOrError<TranscriptionResponse> {
+        Transcription::create(self.build().unwrap()).await
+    }
+}
+
+/// Audio translated into English.
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Translation {
+    pub text: String,
+}
+
+#[derive(Builder, Debug, Clone)]
+#[builder(pattern = "owned")]
+#[builder(name = "TranslationBuilder")]
+#[builder(setter(strip_option, into))]
+pub struct TranslationRequest {
+    /// ID of the model to use. Only `whisper-1` is currently available.
+    pub model: String,
+    /// The local path of the audio file to translate, in one of these formats:
+    /// flac, mp3, mp4, mpeg, mpga, m4a, ogg, wav, or webm.
+    pub file: String,
+    /// An optional text to guide the model's style or continue a previous audio segment.
+    /// The prompt should be in English.
+    #[builder(default)]
+    pub prompt: Option<String>,
+    /// The sampling temperature, between 0 and 1.
+    /// Higher values like 0.8 will make the output more random,
+    /// while lower values like 0.2 will make it more focused and deterministic.
+    #[builder(default)]
+    pub temperature: Option<f32>,
+    /// The credentials to use for this request.
+    #[builder(default)]
+    pub credentials: Option<Credentials>,
+}
+
+impl Translation {
+    async fn create(request: TranslationRequest) -> ApiResponseOrError<Self> {
+        let mut form = Form::new()
+            .text("model", request.model)
+            .part("file", audio_file_part(&request.file).await?);
+        if let Some(prompt) = request.prompt {
+            form = form.text("prompt", prompt);
+        }
+        if let Some(temperature) = request.temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+        openai_post_multipart("audio/translations", form, request.credentials).await
+    }
+
+    pub fn builder(model: impl Into<String>, file: impl Into<String>) -> TranslationBuilder {
+        TranslationBuilder::create_empty().model(model).file(file)
+    }
+}
+
+impl TranslationBuilder {
+    pub async fn create(self) -> ApiResponseOrError<Translation> {
+        Translation::create(self.build().unwrap()).await
+    }
+}
+
+/// Opens the audio file at the given path, failing with an `io` error if it doesn't exist.
+async fn audio_file_part(file: &str) -> ApiResponseOrError<reqwest::multipart::Part> {
+    file_part(file).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dotenvy::dotenv;
+
+    #[tokio::test]
+    async fn speech() {
+        dotenv().ok();
+        let credentials = Credentials::from_env();
+
+        let audio = Speech::builder("tts-1", "Hello, world!", "alloy")
+            .response_format("mp3")
+            .credentials(credentials)
+            .create()
+            .await
+            .unwrap();
+
+        assert!(!audio.is_empty());
+    }
+
+    #[test]
+    fn speech_request_body() {
+        let request = Speech::builder("gpt-4o-mini-tts", "Hello, world!", "coral")
+            .instructions("Speak in a cheerful and positive tone.")
+            .response_format("wav")
+            .speed(1.25)
+            .credentials(Credentials::new("", ""))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "model": "gpt-4o-mini-tts",
+                "input": "Hello, world!",
+                "voice": "coral",
+                "instructions": "Speak in a cheerful and positive tone.",
+                "response_format": "wav",
+                "speed": 1.25,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn speech_speed_out_of_range() {
+        for speed in [0.2, 4.5] {
+            let error = Speech::builder("tts-1", "Hello, world!", "alloy")
+                .speed(speed)
+                .create()
+                .await
+                .unwrap_err();
+            assert_eq!(error.error_type, "invalid_request");
+            assert!(error.message.contains("between 0.25 and 4.0"));
+        }
+        let error = Speech::builder("tts-1", "Hello, world!", "alloy")
+            .speed(5.0)
+            .stream_to(Vec::new())
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_type, "invalid_request");
+        assert!(Speech::builder("tts-1", "Hello, world!", "alloy")
+            .speed(4.0)
+            .build()
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn speech_stream_to() {
+        let audio: Vec<u8> = (0..=255).cycle().take(64 * 1024).collect();
+        let head = format!(
+            "HTTP/1.1 200 OK\r\ncontent-type: audio/mpeg\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            audio.len()
+        );
+        let mut writes = vec![head.into_bytes()];
+        writes.extend(audio.chunks(16 * 1024).map(<[u8]>::to_vec));
+        let (credentials, server) = crate::tests::mock_server_writes(writes).await;
+
+        let mut output = Vec::new();
+        let written = Speech::builder("tts-1", "Hello, world!", "alloy")
+            .credentials(credentials)
+            .stream_to(&mut output)
+            .await
+            .unwrap();
+
+        assert_eq!(written, audio.len() as u64);
+        assert_eq!(output, audio);
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /v1/audio/speech "));
+    }
+
+    #[test]
+    fn verbose_transcription() {
+        let transcription: VerboseTranscription = serde_json::from_str(
+            r#"{
+                "task": "transcribe",
+                "language": "english",
+                "duration": 8.47,
+                "text": "The beach was a popular spot on a hot summer day.",
+                "segments": [{
+                    "id": 0,
+                    "seek": 0,
+                    "start": 0.0,
+                    "end": 3.32,
+                    "text": " The beach was a popular spot on a hot summer day.",
+                    "tokens": [50364, 440, 7534, 390, 257, 3743, 4008, 322, 257, 2368, 4266, 786, 13],
+                    "temperature": 0.0,
+                    "avg_logprob": -0.2860786020755768,
+                    "compression_ratio": 1.2363636493682861,
+                    "no_speech_prob": 0.00985979475080967
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(transcription.language, "english");
+        assert_eq!(transcription.duration, 8.47);
+        assert_eq!(transcription.segments.len(), 1);
+        assert_eq!(transcription.segments[0].end, 3.32);
+        assert!(transcription.words.is_empty());
+    }
+
+    #[tokio::test]
+    async fn detected_language() {
+        let (credentials, server) = crate::tests::mock_server(
+            200,
+            r#"{"task":"transcribe","language":"german","duration":1.5,"text":"Guten Tag."}"#,
+        )
+        .await;
+
+        let transcription = Transcription::builder("whisper-1", "test_data/pixel.png")
+            .response_format(TranscriptionResponseFormat::VerboseJson)
+            .credentials(credentials)
+            .create()
+            .await
+            .unwrap();
+
+        assert_eq!(transcription.detected_language(), Some("german"));
+        assert_eq!(transcription.text(), "Guten Tag.");
+        let request = server.await.unwrap();
+        assert!(!request.contains("name=\"language\""));
+
+        let transcription = TranscriptionResponse::Json(Transcription {
+            text: "Guten Tag.".to_string(),
+        });
+        assert_eq!(transcription.detected_language(), None);
+    }
+
+    #[test]
+    fn transcription_form_fields() {
+        let request = Transcription::builder("gpt-4o-transcribe", "speech.mp3")
+            .response_format(TranscriptionResponseFormat::VerboseJson)
+            .temperature(0.2)
+            .timestamp_granularities([TimestampGranularity::Word, TimestampGranularity::Segment])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            request.text_fields(),
+            [
+                ("model", "gpt-4o-transcribe".to_string()),
+                ("response_format", "verbose_json".to_string()),
+                ("temperature", "0.2".to_string()),
+                ("timestamp_granularities[]", "word".to_string()),
+                ("timestamp_granularities[]", "segment".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn translation_missing_file() {
+        let response = Translation::builder("whisper-1", "test_data/missing_file.mp3")
+            .credentials(Credentials::new("", ""))
+            .create()
+            .await;
+
+        let error = response.unwrap_err();
+        assert_eq!(error.error_type, "io");
+    }
+}
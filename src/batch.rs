@@ -0,0 +1,239 @@
+//! Create large batches of API requests for asynchronous processing.
+//! The Batch API returns completions within 24 hours for a discounted price.
+//!
+//! The input is a JSONL file uploaded with the `batch` purpose via the [`files`](crate::files) module,
+//! and the results are written to an output file which can be downloaded the same way.
+//!
+//! Related guide: [Batch](https://platform.openai.com/docs/guides/batch)
+
+use super::{openai_get, openai_post, openai_request_json, ApiResponseOrError, Credentials};
+use crate::RequestPagination;
+use derive_builder::Builder;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Batch {
+    pub id: String,
+    pub object: String,
+    /// The OpenAI API endpoint used by the batch.
+    pub endpoint: String,
+    pub errors: Option<BatchErrors>,
+    /// The ID of the input file for the batch.
+    pub input_file_id: String,
+    /// The time frame within which the batch should be processed.
+    pub completion_window: String,
+    pub status: BatchStatus,
+    /// The ID of the file containing the outputs of successfully executed requests.
+    pub output_file_id: Option<String>,
+    /// The ID of the file containing the outputs of requests with errors.
+    pub error_file_id: Option<String>,
+    /// Unix timestamp, in seconds, of when the batch was created.
+    pub created_at: u64,
+    pub in_progress_at: Option<u64>,
+    pub expires_at: Option<u64>,
+    pub finalizing_at: Option<u64>,
+    pub completed_at: Option<u64>,
+    pub failed_at: Option<u64>,
+    pub expired_at: Option<u64>,
+    pub cancelling_at: Option<u64>,
+    pub cancelled_at: Option<u64>,
+    /// The request counts for different statuses within the batch.
+    pub request_counts: Option<BatchRequestCounts>,
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Validating,
+    Failed,
+    InProgress,
+    Finalizing,
+    Completed,
+    Expired,
+    Cancelling,
+    Cancelled,
+    /// A status not known to this crate. It is not treated as terminal.
+    #[serde(other)]
+    Unknown,
+}
+
+impl BatchStatus {
+    /// Whether the batch has stopped and its status will not change anymore.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            BatchStatus::Failed
+                | BatchStatus::Completed
+                | BatchStatus::Expired
+                | BatchStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct BatchErrors {
+    pub object: String,
+    pub data: Vec<BatchError>,
+}
+
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct BatchError {
+    pub code: String,
+    pub message: String,
+    pub param: Option<String>,
+    /// The line number of the input file where the error occurred, if applicable.
+    pub line: Option<u64>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BatchRequestCounts {
+    pub total: u64,
+    pub completed: u64,
+    pub failed: u64,
+}
+
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Batches {
+    pub data: Vec<Batch>,
+    pub first_id: Option<String>,
+    pub last_id: Option<String>,
+    pub has_more: bool,
+}
+
+#[derive(Serialize, Builder, Debug, Clone)]
+#[builder(derive(Clone, Debug, PartialEq))]
+#[builder(pattern = "owned")]
+#[builder(name = "BatchBuilder")]
+#[builder(setter(strip_option, into))]
+pub struct BatchRequest {
+    /// The ID of an uploaded file that contains requests for the new batch.
+    /// The file must be uploaded with the purpose `batch`.
+    pub input_file_id: String,
+    /// The endpoint to be used for all requests in the batch.
+    /// Currently `/v1/chat/completions`, `/v1/embeddings`, and `/v1/completions` are supported.
+    pub endpoint: String,
+    /// The time frame within which the batch should be processed. Currently only `24h` is supported.
+    #[builder(default = "String::from(\"24h\")")]
+    pub completion_window: String,
+    /// Set of 16 key-value pairs that can be attached to the batch.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[builder(default)]
+    pub metadata: HashMap<String, String>,
+    /// The credentials to use for this request.
+    #[serde(skip_serializing)]
+    #[builder(default)]
+    pub credentials: Option<Credentials>,
+}
+
+impl Batch {
+    /// Creates and executes a batch from an uploaded file of requests.
+    async fn create(request: BatchRequest) -> ApiResponseOrError<Self> {
+        let credentials_opt = request.credentials.clone();
+        openai_post("batches", &request, credentials_opt).await
+    }
+
+    pub fn builder(input_file_id: impl Into<String>, endpoint: impl Into<String>) -> BatchBuilder {
+        BatchBuilder::create_empty()
+            .input_file_id(input_file_id)
+            .endpoint(endpoint)
+    }
+
+    /// Retrieves a batch.
+    pub async fn fetch(id: &str, credentials: Credentials) -> ApiResponseOrError<Self> {
+        openai_get(&format!("batches/{id}"), Some(credentials)).await
+    }
+
+    /// Cancels an in-progress batch.
+    /// The batch will be in status `cancelling` for up to 10 minutes, before changing to `cancelled`.
+    pub async fn cancel(id: &str, credentials: Credentials) -> ApiResponseOrError<Self> {
+        openai_request_json(
+            Method::POST,
+            &format!("batches/{id}/cancel"),
+            |request| request,
+            Some(credentials),
+        )
+        .await
+    }
+
+    /// List your organization's batches.
+    pub async fn list(
+        pagination: RequestPagination,
+        credentials: Credentials,
+    ) -> ApiResponseOrError<Batches> {
+        openai_request_json(
+            Method::GET,
+            "batches",
+            |request| request.query(&pagination),
+            Some(credentials),
+        )
+        .await
+    }
+}
+
+impl BatchBuilder {
+    pub async fn create(self) -> ApiResponseOrError<Batch> {
+        Batch::create(self.build().unwrap()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_request_body() {
+        let request = Batch::builder("file-abc123", "/v1/chat/completions")
+            .metadata([("customer_id".to_string(), "user_123".to_string())])
+            .credentials(Credentials::new("", ""))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "input_file_id": "file-abc123",
+                "endpoint": "/v1/chat/completions",
+                "completion_window": "24h",
+                "metadata": { "customer_id": "user_123" },
+            })
+        );
+    }
+
+    #[test]
+    fn batch_deserialization() {
+        let batch: Batch = serde_json::from_value(serde_json::json!({
+            "id": "batch_abc123",
+            "object": "batch",
+            "endpoint": "/v1/completions",
+            "errors": null,
+            "input_file_id": "file-abc123",
+            "completion_window": "24h",
+            "status": "completed",
+            "output_file_id": "file-cvaTdG",
+            "error_file_id": "file-HOWS94",
+            "created_at": 1711471533,
+            "in_progress_at": 1711471538,
+            "expires_at": 1711557933,
+            "finalizing_at": 1711493133,
+            "completed_at": 1711493163,
+            "failed_at": null,
+            "expired_at": null,
+            "cancelling_at": null,
+            "cancelled_at": null,
+            "request_counts": { "total": 100, "completed": 95, "failed": 5 },
+            "metadata": null,
+        }))
+        .unwrap();
+
+        assert!(batch.status.is_terminal());
+        assert_eq!(batch.output_file_id.as_deref(), Some("file-cvaTdG"));
+        assert_eq!(batch.request_counts.unwrap().failed, 5);
+
+        let status: BatchStatus = serde_json::from_value(serde_json::json!("paused")).unwrap();
+        assert_eq!(status, BatchStatus::Unknown);
+        assert!(!status.is_terminal());
+    }
+}
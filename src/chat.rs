@@ -1,44 +1,202 @@
 //! Given a chat conversation, the model will return a chat completion response.
 
-use super::{openai_post, ApiResponseOrError, Credentials, Usage};
-use crate::openai_request_stream;
+use super::{openai_delete, openai_get, openai_post, ApiResponseOrError, Credentials, Usage};
+use crate::{
+    credentials_with_client, mime_type, openai_request_json, openai_request_stream, OpenAiError,
+    RequestPagination, SamplingParams,
+};
+use base64::prelude::{Engine, BASE64_STANDARD};
 use derive_builder::Builder;
 use futures_util::StreamExt;
-use reqwest::Method;
+use reqwest::{Client, Method};
 use reqwest_eventsource::{CannotCloneRequestError, Event, EventSource};
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::collections::HashMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::task::AbortHandle;
 
 /// A full chat completion.
 pub type ChatCompletion = ChatCompletionGeneric<ChatCompletionChoice>;
 
 /// A delta chat completion, which is streamed token by token.
+///
+/// A delta may have no choices at all, such as the final usage chunk or
+/// keep-alive chunks sent by some gateways, so avoid indexing `choices` directly.
 pub type ChatCompletionDelta = ChatCompletionGeneric<ChatCompletionChoiceDelta>;
 
-#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+#[derive(Deserialize, Clone, Debug, PartialEq)]
 pub struct ChatCompletionGeneric<C> {
     pub id: String,
     pub object: String,
     pub created: u64,
     pub model: String,
+    #[serde(default = "Vec::new")]
     pub choices: Vec<C>,
     pub usage: Option<Usage>,
+    /// The service tier that processed the request, such as `default` or `flex`.
+    #[serde(default)]
+    pub service_tier: Option<String>,
+    /// The backend configuration the model ran with. Together with `seed`, this shows
+    /// whether repeated requests can be expected to return the same result:
+    /// if it changed, so may have the output.
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
+    /// Any other fields of the response, such as fields specific to an OpenAI-compatible provider.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
-#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+#[derive(Deserialize, Clone, Debug, PartialEq)]
 pub struct ChatCompletionChoice {
     pub index: u64,
-    pub finish_reason: String,
+    /// The reason the model stopped generating tokens.
+    /// This may be `None` for choices aggregated from an unfinished stream,
+    /// or with providers that omit it.
+    pub finish_reason: Option<FinishReason>,
     pub message: ChatCompletionMessage,
+    /// Log probability information for the choice, if `logprobs` was requested.
+    pub logprobs: Option<ChatCompletionLogprobs>,
+}
+
+impl ChatCompletionChoice {
+    /// Deserializes the message content of a structured output, such as one requested
+    /// with a `json_schema` response format, into `T`.
+    pub fn parsed<T: DeserializeOwned>(&self) -> Result<T, StructuredOutputError> {
+        if let Some(refusal) = &self.message.refusal {
+            return Err(StructuredOutputError::Refusal(refusal.clone()));
+        }
+        let content = self
+            .message
+            .content
+            .as_ref()
+            .ok_or(StructuredOutputError::MissingContent)?;
+        serde_json::from_str(&content.text()).map_err(StructuredOutputError::Deserialize)
+    }
+}
+
+/// An error returned by [`ChatCompletionChoice::parsed`].
+#[derive(Debug)]
+pub enum StructuredOutputError {
+    /// The model refused to answer, explaining why.
+    Refusal(String),
+    /// The message has no content.
+    MissingContent,
+    /// The content does not match the requested type.
+    Deserialize(serde_json::Error),
+}
+
+impl std::fmt::Display for StructuredOutputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StructuredOutputError::Refusal(refusal) => write!(f, "Model refused: {refusal}"),
+            StructuredOutputError::MissingContent => f.write_str("Message has no content"),
+            StructuredOutputError::Deserialize(error) => {
+                write!(f, "Failed to deserialize content: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for StructuredOutputError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StructuredOutputError::Deserialize(error) => Some(error),
+            _ => None,
+        }
+    }
 }
 
+/// The reason the model stopped generating tokens.
 #[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(from = "String")]
+pub enum FinishReason {
+    /// The model reached a natural stopping point or a stop sequence.
+    Stop,
+    /// The output was truncated by the maximum number of tokens or the context length.
+    Length,
+    /// The model called one or more tools.
+    ToolCalls,
+    /// Content was omitted by a content filter.
+    ContentFilter,
+    /// The model called a function, with the deprecated `functions` parameter.
+    FunctionCall,
+    /// A reason not known to this crate, such as one specific to a provider.
+    Other(String),
+}
+
+impl FinishReason {
+    pub fn as_str(&self) -> &str {
+        match self {
+            FinishReason::Stop => "stop",
+            FinishReason::Length => "length",
+            FinishReason::ToolCalls => "tool_calls",
+            FinishReason::ContentFilter => "content_filter",
+            FinishReason::FunctionCall => "function_call",
+            FinishReason::Other(reason) => reason,
+        }
+    }
+}
+
+impl From<String> for FinishReason {
+    fn from(reason: String) -> Self {
+        match reason.as_str() {
+            "stop" => FinishReason::Stop,
+            "length" => FinishReason::Length,
+            "tool_calls" => FinishReason::ToolCalls,
+            "content_filter" => FinishReason::ContentFilter,
+            "function_call" => FinishReason::FunctionCall,
+            _ => FinishReason::Other(reason),
+        }
+    }
+}
+
+impl std::fmt::Display for FinishReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
 pub struct ChatCompletionChoiceDelta {
     pub index: u64,
-    pub finish_reason: Option<String>,
+    /// The reason the model stopped generating tokens, only set in the last delta of a choice.
+    pub finish_reason: Option<FinishReason>,
     pub delta: ChatCompletionMessageDelta,
+    /// Log probability information for the tokens in this delta, if `logprobs` was requested.
+    pub logprobs: Option<ChatCompletionLogprobs>,
+}
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ChatCompletionLogprobs {
+    /// A list of message content tokens with log probability information.
+    pub content: Option<Vec<TokenLogprob>>,
+    /// A list of message refusal tokens with log probability information.
+    pub refusal: Option<Vec<TokenLogprob>>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct TokenLogprob {
+    pub token: String,
+    /// The log probability of this token, or `-9999.0` if it is not in the top 20 most likely tokens.
+    pub logprob: f64,
+    /// The UTF-8 bytes representation of the token.
+    /// Useful when characters are represented by multiple tokens.
+    pub bytes: Option<Vec<u8>>,
+    /// The most likely tokens and their log probability at this token position.
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f64,
+    pub bytes: Option<Vec<u8>>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Default)]
@@ -48,8 +206,9 @@ pub struct ChatCompletionMessage {
     /// The contents of the message
     ///
     /// This is always required for all messages, except for when ChatGPT calls
-    /// a function.
-    pub content: Option<String>,
+    /// a function. When it is `None` on an assistant message, check [`refusal`](Self::refusal)
+    /// for the reason the model declined to answer.
+    pub content: Option<Content>,
     /// The name of the user in a multi-user chat
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
@@ -70,6 +229,13 @@ pub struct ChatCompletionMessage {
         default = "default_tool_calls_deserialization"
     )]
     pub tool_calls: Vec<ToolCall>,
+    /// The refusal message of the assistant, set instead of the content
+    /// when the model declines a structured output request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refusal: Option<String>,
+    /// Annotations on the content, such as citations of web search results.
+    #[serde(default, skip_serializing_if = "<[_]>::is_empty")]
+    pub annotations: Vec<Annotation>,
 }
 
 /// Same as ChatCompletionMessage, but received during a response stream.
@@ -94,11 +260,217 @@ pub struct ChatCompletionMessageDelta {
     /// Tool calls that the assistant is requesting to invoke.
     /// Can only be populated if the role is `Assistant`,
     /// otherwise it should be empty.
-    #[serde(
-        skip_serializing_if = "<[_]>::is_empty",
-        default = "default_tool_calls_deserialization"
-    )]
-    pub tool_calls: Vec<ToolCall>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty", default)]
+    pub tool_calls: Vec<ToolCallDelta>,
+    /// The refusal message of the assistant, streamed like the content.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refusal: Option<String>,
+    /// Annotations on the content, such as citations of web search results.
+    #[serde(default, skip_serializing_if = "<[_]>::is_empty")]
+    pub annotations: Vec<Annotation>,
+}
+
+/// The contents of a chat message.
+///
+/// Text-only content is sent as a plain string, while content containing
+/// other parts, such as images for vision models, is sent as an array of parts.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Content {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text {
+        text: String,
+        /// Prompt caching marker, only supported by some providers and gateways.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+    ImageUrl {
+        image_url: ImageUrl,
+        /// Prompt caching marker, only supported by some providers and gateways.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        cache_control: Option<CacheControl>,
+    },
+}
+
+/// Marks a content part as a prompt cache breakpoint for providers that support it,
+/// such as Anthropic-compatible gateways. The OpenAI API caches prompts automatically
+/// and does not accept this annotation.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct CacheControl {
+    /// The type of cache control. Currently, only `ephemeral` is supported.
+    pub r#type: String,
+}
+
+impl CacheControl {
+    pub fn ephemeral() -> Self {
+        CacheControl {
+            r#type: "ephemeral".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct ImageUrl {
+    /// Either a URL of the image or the base64 encoded image data,
+    /// e.g. `data:image/jpeg;base64,...`.
+    pub url: String,
+    /// Specifies the detail level of the image.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<ImageDetail>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    Auto,
+    Low,
+    High,
+}
+
+impl Content {
+    pub fn new_str(text: impl Into<String>) -> Self {
+        Content::Text(text.into())
+    }
+
+    /// Content consisting of a single image, using the default detail level.
+    pub fn new_image_url(url: impl Into<String>) -> Self {
+        Content::Parts(vec![ContentPart::image_url(url)])
+    }
+
+    /// Content consisting of a single local image, inlined as a base64 data URL.
+    /// See [`ContentPart::image_from_path`].
+    pub async fn image_from_path(
+        path: impl AsRef<Path>,
+        detail: Option<ImageDetail>,
+    ) -> ApiResponseOrError<Self> {
+        Ok(Content::Parts(vec![
+            ContentPart::image_from_path(path, detail).await?,
+        ]))
+    }
+
+    pub fn new_parts(parts: Vec<ContentPart>) -> Self {
+        Content::Parts(parts)
+    }
+
+    /// Text content marked with an ephemeral cache breakpoint, intended for long
+    /// developer or system instructions that are repeated across requests.
+    pub fn new_cached_str(text: impl Into<String>) -> Self {
+        Content::Parts(vec![
+            ContentPart::text(text).with_cache_control(CacheControl::ephemeral())
+        ])
+    }
+
+    /// The text of this content, with the text of all parts concatenated.
+    /// Non-text parts are ignored.
+    pub fn text(&self) -> String {
+        match self {
+            Content::Text(text) => text.clone(),
+            Content::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text, .. } => Some(text.as_str()),
+                    ContentPart::ImageUrl { .. } => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ContentPart {
+    pub fn text(text: impl Into<String>) -> Self {
+        ContentPart::Text {
+            text: text.into(),
+            cache_control: None,
+        }
+    }
+
+    pub fn image_url(url: impl Into<String>) -> Self {
+        ContentPart::ImageUrl {
+            image_url: ImageUrl {
+                url: url.into(),
+                detail: None,
+            },
+            cache_control: None,
+        }
+    }
+
+    pub fn image_url_with_detail(url: impl Into<String>, detail: ImageDetail) -> Self {
+        ContentPart::ImageUrl {
+            image_url: ImageUrl {
+                url: url.into(),
+                detail: Some(detail),
+            },
+            cache_control: None,
+        }
+    }
+
+    /// Reads a local PNG, JPEG, GIF or WebP image and inlines it as a base64 data URL.
+    /// The MIME type is detected from the file extension.
+    pub async fn image_from_path(
+        path: impl AsRef<Path>,
+        detail: Option<ImageDetail>,
+    ) -> ApiResponseOrError<Self> {
+        let path = path.as_ref();
+        let mime = mime_type(path);
+        if !mime.starts_with("image/") {
+            return Err(OpenAiError::new(
+                format!("unsupported image type: {}", path.display()),
+                "invalid_image".to_string(),
+            ));
+        }
+        let data = tokio::fs::read(path).await?;
+        Ok(ContentPart::ImageUrl {
+            image_url: ImageUrl {
+                url: format!("data:{mime};base64,{}", BASE64_STANDARD.encode(data)),
+                detail,
+            },
+            cache_control: None,
+        })
+    }
+
+    pub fn with_cache_control(mut self, cache_control: CacheControl) -> Self {
+        match &mut self {
+            ContentPart::Text {
+                cache_control: part_cache_control,
+                ..
+            }
+            | ContentPart::ImageUrl {
+                cache_control: part_cache_control,
+                ..
+            } => *part_cache_control = Some(cache_control),
+        }
+        self
+    }
+}
+
+impl From<String> for Content {
+    fn from(text: String) -> Self {
+        Content::Text(text)
+    }
+}
+
+impl From<&str> for Content {
+    fn from(text: &str) -> Self {
+        Content::Text(text.to_string())
+    }
+}
+
+impl PartialEq<str> for Content {
+    fn eq(&self, other: &str) -> bool {
+        matches!(self, Content::Text(text) if text == other)
+    }
+}
+
+impl PartialEq<&str> for Content {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
@@ -123,6 +495,58 @@ pub struct ToolCallFunction {
     pub arguments: String,
 }
 
+/// Same as ToolCall, but received during a response stream.
+/// The fields of a tool call are spread across the deltas sharing its `index`.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct ToolCallDelta {
+    /// The position of the tool call in the message's list of tool calls.
+    pub index: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<ChatCompletionFunctionCallDelta>,
+}
+
+impl ToolCallDelta {
+    /// Merges a later fragment of the same tool call into `self`.
+    fn merge(&mut self, other: &ToolCallDelta) {
+        if self.id.is_none() {
+            self.id = other.id.clone();
+        }
+        if self.r#type.is_none() {
+            self.r#type = other.r#type.clone();
+        }
+        if let Some(other_function) = &other.function {
+            let function = self.function.get_or_insert_with(Default::default);
+            if function.name.is_none() {
+                function.name = other_function.name.clone();
+            }
+            if let Some(other_arguments) = &other_function.arguments {
+                function
+                    .arguments
+                    .get_or_insert_with(String::new)
+                    .push_str(other_arguments);
+            }
+        }
+    }
+}
+
+impl From<ToolCallDelta> for ToolCall {
+    fn from(delta: ToolCallDelta) -> Self {
+        let function = delta.function.unwrap_or_default();
+        ToolCall {
+            id: delta.id.unwrap_or_default(),
+            r#type: delta.r#type.unwrap_or_else(|| "function".to_string()),
+            function: ToolCallFunction {
+                name: function.name.unwrap_or_default(),
+                arguments: function.arguments.unwrap_or_default(),
+            },
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 pub struct ChatCompletionFunctionDefinition {
     /// The name of the function
@@ -137,6 +561,107 @@ pub struct ChatCompletionFunctionDefinition {
     pub parameters: Option<Value>,
 }
 
+/// A tool the model may call, serialized with its `type` tag.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatCompletionTool {
+    /// A function defined by the application, which runs it when the model calls it.
+    Function {
+        function: ChatCompletionFunctionDefinition,
+    },
+    /// The built-in search over uploaded files, where supported by the provider.
+    FileSearch,
+    /// The built-in sandbox for running code, where supported by the provider.
+    CodeInterpreter,
+    /// The built-in web search. Cited sources are returned in the message
+    /// [`annotations`](ChatCompletionMessage::annotations).
+    WebSearchPreview {
+        /// How much context is retrieved from the web for the answer.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        search_context_size: Option<SearchContextSize>,
+    },
+}
+
+impl ChatCompletionTool {
+    pub fn function(function: ChatCompletionFunctionDefinition) -> Self {
+        ChatCompletionTool::Function { function }
+    }
+
+    /// The built-in web search, with the default search context size.
+    pub fn web_search() -> Self {
+        ChatCompletionTool::WebSearchPreview {
+            search_context_size: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchContextSize {
+    Low,
+    Medium,
+    High,
+}
+
+/// An annotation on a message, such as a citation of a web search result.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Annotation {
+    UrlCitation {
+        url_citation: UrlCitation,
+    },
+    /// An annotation type not known to this crate.
+    #[serde(other)]
+    Unknown,
+}
+
+/// A web page cited in a message.
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct UrlCitation {
+    /// The index of the first character of the citation in the message content.
+    pub start_index: u32,
+    /// The index after the last character of the citation in the message content.
+    pub end_index: u32,
+    pub url: String,
+    pub title: String,
+}
+
+/// Controls which (if any) tool is called by the model.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ToolChoice {
+    /// The model will not call any tool and instead generates a message.
+    None,
+    /// The model can pick between generating a message or calling one or more tools.
+    Auto,
+    /// The model must call one or more tools.
+    Required,
+    /// Forces the model to call the function with the given name.
+    Function(String),
+    /// Restricts the model to the functions with the given names,
+    /// leaving it free to pick between them or to generate a message.
+    AllowedTools(Vec<String>),
+}
+
+impl Serialize for ToolChoice {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let function = |name: &str| json!({ "type": "function", "function": { "name": name } });
+        match self {
+            ToolChoice::None => serializer.serialize_str("none"),
+            ToolChoice::Auto => serializer.serialize_str("auto"),
+            ToolChoice::Required => serializer.serialize_str("required"),
+            ToolChoice::Function(name) => function(name).serialize(serializer),
+            ToolChoice::AllowedTools(names) => json!({
+                "type": "allowed_tools",
+                "allowed_tools": {
+                    "mode": "auto",
+                    "tools": names.iter().map(|name| function(name)).collect::<Vec<_>>(),
+                },
+            })
+            .serialize(serializer),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct ChatCompletionFunctionCall {
     /// The name of the function ChatGPT called
@@ -147,7 +672,7 @@ pub struct ChatCompletionFunctionCall {
 }
 
 /// Same as ChatCompletionFunctionCall, but received during a response stream.
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
 pub struct ChatCompletionFunctionCallDelta {
     /// The name of the function ChatGPT called
     pub name: Option<String>,
@@ -160,6 +685,9 @@ pub struct ChatCompletionFunctionCallDelta {
 #[serde(rename_all = "lowercase")]
 pub enum ChatCompletionMessageRole {
     System,
+    /// Instructions to the model that take priority over user messages,
+    /// replacing `System` for o1 and newer models.
+    Developer,
     User,
     Assistant,
     Function,
@@ -171,24 +699,18 @@ pub enum ChatCompletionMessageRole {
 #[builder(pattern = "owned")]
 #[builder(name = "ChatCompletionBuilder")]
 #[builder(setter(strip_option, into))]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct ChatCompletionRequest {
     /// ID of the model to use. Currently, only `gpt-3.5-turbo`, `gpt-3.5-turbo-0301` and `gpt-4`
     /// are supported.
+    ///
+    /// Defaults to the default model of the credentials, or the `OPENAI_DEFAULT_MODEL` env variable.
+    #[builder(
+        default = "crate::default_model(self.credentials.as_ref().and_then(Option::as_ref))?"
+    )]
     model: String,
     /// The messages to generate chat completions for, in the [chat format](https://platform.openai.com/docs/guides/chat/introduction).
     messages: Vec<ChatCompletionMessage>,
-    /// What sampling temperature to use, between 0 and 2. Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic.
-    ///
-    /// We generally recommend altering this or `top_p` but not both.
-    #[builder(default)]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    temperature: Option<f32>,
-    /// An alternative to sampling with temperature, called nucleus sampling, where the model considers the results of the tokens with top_p probability mass. So 0.1 means only the tokens comprising the top 10% probability mass are considered.
-    ///
-    /// We generally recommend altering this or `temperature` but not both.
-    #[builder(default)]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    top_p: Option<f32>,
     /// How many chat completion choices to generate for each input message.
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -196,36 +718,53 @@ pub struct ChatCompletionRequest {
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     stream: Option<bool>,
-    /// Up to 4 sequences where the API will stop generating further tokens.
+    /// Options for streaming responses. Only set this when streaming.
     #[builder(default)]
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    /// Whether to remove a `stop` sequence from the end of the returned content.
+    /// The OpenAI API never includes it, but some compatible providers do.
+    ///
+    /// Applies to `create` and `create_stream_resilient`. The deltas of `create_stream`
+    /// are sent as they arrive and are never stripped.
+    #[serde(skip_serializing)]
+    #[builder(default)]
+    strip_stop_sequences: bool,
     /// This feature is in Beta. If specified, our system will make a best effort to sample deterministically, such that repeated requests with the same seed and parameters should return the same result. Determinism is not guaranteed, and you should refer to the system_fingerprint response parameter to monitor changes in the backend.
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     seed: Option<u64>,
-    /// The maximum number of tokens allowed for the generated answer. By default, the number of tokens the model can return will be (4096 - prompt tokens).
+    /// Constrains effort on reasoning for reasoning models, such as the o-series.
+    /// Reducing reasoning effort can result in faster responses and fewer tokens used on reasoning.
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
-    max_tokens: Option<u64>,
-    /// Number between -2.0 and 2.0. Positive values penalize new tokens based on whether they appear in the text so far, increasing the model's likelihood to talk about new topics.
+    reasoning_effort: Option<ReasoningEffort>,
+    /// The maximum number of tokens allowed for the generated answer. By default, the number of tokens the model can return will be (4096 - prompt tokens).
     ///
-    /// [See more information about frequency and presence penalties.](https://platform.openai.com/docs/api-reference/parameter-details)
+    /// This is deprecated in favor of `max_completion_tokens`, and is not compatible with o-series models,
+    /// so for those it is sent as `max_completion_tokens` instead, unless that is set.
+    /// Use it only for legacy models or providers which do not support `max_completion_tokens`.
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
-    presence_penalty: Option<f32>,
-    /// Number between -2.0 and 2.0. Positive values penalize new tokens based on their existing frequency in the text so far, decreasing the model's likelihood to repeat the same line verbatim.
+    max_tokens: Option<u64>,
+    /// An upper bound for the number of tokens that can be generated for a completion,
+    /// including visible output tokens and reasoning tokens.
     ///
-    /// [See more information about frequency and presence penalties.](https://platform.openai.com/docs/api-reference/parameter-details)
+    /// Prefer this over `max_tokens` for gpt-4o and newer models, and use it for o-series models, which reject `max_tokens`.
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
-    frequency_penalty: Option<f32>,
-    /// Modify the likelihood of specified tokens appearing in the completion.
-    ///
-    /// Accepts a json object that maps tokens (specified by their token ID in the tokenizer) to an associated bias value from -100 to 100. Mathematically, the bias is added to the logits generated by the model prior to sampling. The exact effect will vary per model, but values between -1 and 1 should decrease or increase likelihood of selection; values like -100 or 100 should result in a ban or exclusive selection of the relevant token.
+    max_completion_tokens: Option<u64>,
+    /// Whether to return log probabilities of the output tokens or not.
+    /// If true, returns the log probabilities of each output token returned in the `content` of `message`.
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
-    logit_bias: Option<HashMap<String, f32>>,
+    logprobs: Option<bool>,
+    /// An integer between 0 and 20 specifying the number of most likely tokens to return at each token position,
+    /// each with an associated log probability. `logprobs` must be set to `true` if this parameter is used,
+    /// so setting this enables `logprobs` unless it was explicitly disabled.
+    #[builder(default, setter(custom))]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_logprobs: Option<u8>,
     /// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse. [Learn more](https://platform.openai.com/docs/guides/safety-best-practices/end-user-ids).
     #[builder(default)]
     #[serde(skip_serializing_if = "String::is_empty")]
@@ -251,12 +790,42 @@ pub struct ChatCompletionRequest {
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     function_call: Option<Value>,
+    /// A list of tools the model may call. Currently, only functions are supported as a tool.
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ChatCompletionTool>,
+    /// Controls which (if any) tool is called by the model.
+    ///
+    /// `none` is the default when no tools are present. `auto` is the default if tools are present.
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<ToolChoice>,
     /// An object specifying the format that the model must output. Compatible with GPT-4 Turbo and all GPT-3.5 Turbo models newer than gpt-3.5-turbo-1106.
     /// Setting to { "type": "json_object" } enables JSON mode, which guarantees the message the model generates is valid JSON.
     /// Important: when using JSON mode, you must also instruct the model to produce JSON yourself via a system or user message. Without this, the model may generate an unending stream of whitespace until the generation reaches the token limit, resulting in a long-running and seemingly "stuck" request. Also note that the message content may be partially cut off if finish_reason="length", which indicates the generation exceeded max_tokens or the conversation exceeded the max context length.
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<ChatCompletionResponseFormat>,
+    /// Whether or not to store the output of this chat completion request,
+    /// so that it can later be retrieved, listed and used for distillation or evals.
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    store: Option<bool>,
+    /// Set of 16 key-value pairs that can be attached to a stored completion,
+    /// useful for filtering when listing stored completions.
+    #[builder(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    metadata: HashMap<String, String>,
+    /// The latency tier to process the request with. Defaults to `auto`.
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    service_tier: Option<ServiceTier>,
+    /// Predicted output, such as the current version of a file being edited.
+    /// Speeds up responses when most of the output is known in advance.
+    /// See `accepted_prediction_tokens` and `rejected_prediction_tokens` in the usage.
+    #[builder(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prediction: Option<Prediction>,
     /// The credentials to use for this request.
     #[serde(skip_serializing)]
     #[builder(default)]
@@ -266,56 +835,503 @@ pub struct ChatCompletionRequest {
     #[builder(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     venice_parameters: Option<VeniceParameters>,
+    /// Sampling parameters, such as the temperature and stop sequences.
+    #[serde(flatten)]
+    #[builder(default, setter(custom))]
+    sampling: SamplingParams,
+    /// A cache to look the completion up in before sending the request, and to store it in after.
+    #[serde(skip_serializing)]
+    #[builder(default)]
+    cache: Option<ChatCompletionCache>,
 }
 
-#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
-pub struct VeniceParameters {
-    pub include_venice_system_prompt: bool,
+impl ChatCompletionRequest {
+    /// Moves `max_tokens` to `max_completion_tokens` for reasoning models, which reject `max_tokens`,
+    /// unless `max_completion_tokens` is already set.
+    fn remap_max_tokens(&mut self) {
+        if is_reasoning_model(&self.model) && self.max_completion_tokens.is_none() {
+            self.max_completion_tokens = self.max_tokens.take();
+        }
+    }
+
+    /// The request body sent to the API, with object keys sorted.
+    fn body(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
 }
 
-#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
-pub struct ChatCompletionResponseFormat {
-    /// Must be one of text or json_object (defaults to text)
-    #[serde(rename = "type")]
-    typ: String,
+/// Requests are equal when they send the same body and post-process the response the same way,
+/// so that identical concurrent requests can be coalesced into one API call.
+/// The credentials and the cache are ignored.
+impl PartialEq for ChatCompletionRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.strip_stop_sequences == other.strip_stop_sequences && self.body() == other.body()
+    }
 }
 
-impl ChatCompletionResponseFormat {
-    pub fn json_object() -> Self {
-        ChatCompletionResponseFormat {
-            typ: "json_object".to_string(),
+impl Eq for ChatCompletionRequest {}
+
+impl Hash for ChatCompletionRequest {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.strip_stop_sequences.hash(state);
+        self.body().to_string().hash(state);
+    }
+}
+
+/// Whether the model is a reasoning model, such as the o-series and gpt-5, judging by its ID.
+fn is_reasoning_model(model: &str) -> bool {
+    let model = model.strip_prefix("ft:").unwrap_or(model);
+    let mut chars = model.chars();
+    let o_series = chars.next() == Some('o')
+        && chars.next().is_some_and(|c| c.is_ascii_digit())
+        && matches!(chars.next(), None | Some('-'));
+    o_series || model.starts_with("gpt-5")
+}
+
+/// An in-memory least recently used cache of chat completions, keyed on a hash of the request
+/// (excluding its credentials).
+///
+/// This is only useful for deterministic requests, such as those with a temperature of 0,
+/// as a hit returns the same completion for every identical request.
+/// Clones share the same cache.
+#[derive(Clone)]
+pub struct ChatCompletionCache {
+    capacity: usize,
+    entries: Arc<Mutex<ChatCompletionCacheEntries>>,
+}
+
+#[derive(Default)]
+struct ChatCompletionCacheEntries {
+    completions: HashMap<u64, ChatCompletion>,
+    /// Keys from least to most recently used.
+    order: VecDeque<u64>,
+}
+
+impl ChatCompletionCache {
+    /// Creates an empty cache which holds up to `capacity` completions.
+    pub fn new(capacity: usize) -> Self {
+        ChatCompletionCache {
+            capacity,
+            entries: Default::default(),
         }
     }
 
-    pub fn text() -> Self {
-        ChatCompletionResponseFormat {
-            typ: "text".to_string(),
+    /// The number of completions in the cache.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().completions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn key(request: &ChatCompletionRequest) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        request.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn get(&self, key: u64) -> Option<ChatCompletion> {
+        let mut entries = self.entries.lock().unwrap();
+        let completion = entries.completions.get(&key)?.clone();
+        entries.order.retain(|k| *k != key);
+        entries.order.push_back(key);
+        Some(completion)
+    }
+
+    fn insert(&self, key: u64, completion: ChatCompletion) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.completions.insert(key, completion).is_some() {
+            entries.order.retain(|k| *k != key);
+        }
+        entries.order.push_back(key);
+        while entries.order.len() > self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.completions.remove(&oldest);
+            }
         }
     }
 }
 
-impl<C> ChatCompletionGeneric<C> {
-    pub fn builder(
-        model: &str,
-        messages: impl Into<Vec<ChatCompletionMessage>>,
-    ) -> ChatCompletionBuilder {
-        ChatCompletionBuilder::create_empty()
-            .model(model)
-            .messages(messages)
+impl std::fmt::Debug for ChatCompletionCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatCompletionCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
     }
 }
 
-impl ChatCompletion {
-    pub async fn create(request: ChatCompletionRequest) -> ApiResponseOrError<Self> {
-        let credentials_opt = request.credentials.clone();
-        openai_post("chat/completions", &request, credentials_opt).await
+impl PartialEq for ChatCompletionCache {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.entries, &other.entries)
     }
 }
 
-impl ChatCompletionDelta {
-    pub async fn create(
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct DeletedChatCompletion {
+    pub id: String,
+    pub object: String,
+    pub deleted: bool,
+}
+
+/// A page of stored chat completions.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatCompletions {
+    pub data: Vec<ChatCompletion>,
+    pub first_id: Option<String>,
+    pub last_id: Option<String>,
+    pub has_more: bool,
+}
+
+/// Filters for listing chat completions stored with `store(true)`.
+#[derive(Builder, Debug, Clone)]
+#[builder(pattern = "owned")]
+#[builder(name = "ChatCompletionsBuilder")]
+#[builder(setter(strip_option, into))]
+pub struct ChatCompletionsRequest {
+    /// Only list completions generated by this model.
+    #[builder(default)]
+    pub model: Option<String>,
+    /// Only list completions with all of these metadata key-value pairs.
+    #[builder(default)]
+    pub metadata: HashMap<String, String>,
+    #[builder(default)]
+    pub pagination: RequestPagination,
+    /// The credentials to use for this request.
+    #[builder(default)]
+    pub credentials: Option<Credentials>,
+}
+
+impl ChatCompletions {
+    /// Lists stored chat completions.
+    async fn list(request: ChatCompletionsRequest) -> ApiResponseOrError<Self> {
+        let mut query = Vec::new();
+        if let Some(model) = request.model {
+            query.push(("model".to_string(), model));
+        }
+        for (key, value) in request.metadata {
+            query.push((format!("metadata[{key}]"), value));
+        }
+        openai_request_json(
+            Method::GET,
+            "chat/completions",
+            |builder| builder.query(&request.pagination).query(&query),
+            request.credentials,
+        )
+        .await
+    }
+
+    pub fn builder() -> ChatCompletionsBuilder {
+        ChatCompletionsBuilder::create_empty()
+    }
+}
+
+impl ChatCompletionsBuilder {
+    pub async fn list(self) -> ApiResponseOrError<ChatCompletions> {
+        ChatCompletions::list(self.build().unwrap()).await
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceTier {
+    /// Uses the scale tier if the project has it, and the default tier otherwise.
+    Auto,
+    Default,
+    /// Lower prices in exchange for slower responses and occasional unavailability.
+    Flex,
+}
+
+/// Predicted output for [Predicted Outputs](https://platform.openai.com/docs/guides/predicted-outputs).
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Prediction {
+    /// Content that is expected to largely match the output.
+    Content { content: String },
+}
+
+impl From<String> for Prediction {
+    fn from(content: String) -> Self {
+        Prediction::Content { content }
+    }
+}
+
+impl From<&str> for Prediction {
+    fn from(content: &str) -> Self {
+        Prediction::Content {
+            content: content.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct StreamOptions {
+    /// If set, an additional chunk will be streamed before the end of the stream,
+    /// with `usage` set for the entire request and an empty `choices` array.
+    pub include_usage: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct VeniceParameters {
+    pub include_venice_system_prompt: bool,
+}
+
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct ChatCompletionResponseFormat {
+    /// One of text, json_object or json_schema (defaults to text)
+    #[serde(rename = "type")]
+    typ: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    json_schema: Option<ResponseJsonSchema>,
+}
+
+/// A JSON schema that the model output must conform to, for structured outputs.
+#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct ResponseJsonSchema {
+    /// The name of the response format, used by the model to understand its purpose.
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The schema, described as a JSON Schema object.
+    pub schema: Value,
+    /// Whether to enforce the schema exactly. Strict schemas only support a subset of
+    /// JSON Schema, and require `additionalProperties: false` and all properties to be required.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
+}
+
+impl ChatCompletionResponseFormat {
+    pub fn json_object() -> Self {
+        ChatCompletionResponseFormat {
+            typ: "json_object".to_string(),
+            json_schema: None,
+        }
+    }
+
+    pub fn text() -> Self {
+        ChatCompletionResponseFormat {
+            typ: "text".to_string(),
+            json_schema: None,
+        }
+    }
+
+    pub fn json_schema(name: impl Into<String>, schema: Value, strict: bool) -> Self {
+        ChatCompletionResponseFormat {
+            typ: "json_schema".to_string(),
+            json_schema: Some(ResponseJsonSchema {
+                name: name.into(),
+                description: None,
+                schema,
+                strict: Some(strict),
+            }),
+        }
+    }
+}
+
+impl<C> ChatCompletionGeneric<C> {
+    pub fn builder(
+        model: &str,
+        messages: impl Into<Vec<ChatCompletionMessage>>,
+    ) -> ChatCompletionBuilder {
+        ChatCompletionBuilder::create_empty()
+            .model(model)
+            .messages(messages)
+    }
+
+    /// Same as [`builder`](Self::builder), but using the default model.
+    /// See [`Credentials::with_default_model`].
+    pub fn builder_with_default_model(
+        messages: impl Into<Vec<ChatCompletionMessage>>,
+    ) -> ChatCompletionBuilder {
+        ChatCompletionBuilder::create_empty().messages(messages)
+    }
+}
+
+impl ChatCompletion {
+    pub async fn create(mut request: ChatCompletionRequest) -> ApiResponseOrError<Self> {
+        request.remap_max_tokens();
+        let cache_key = request
+            .cache
+            .as_ref()
+            .map(|_| ChatCompletionCache::key(&request));
+        if let (Some(cache), Some(key)) = (&request.cache, cache_key) {
+            if let Some(completion) = cache.get(key) {
+                return Ok(completion);
+            }
+        }
+        let credentials_opt = request.credentials.clone();
+        let mut completion: Self =
+            openai_post("chat/completions", &request, credentials_opt).await?;
+        if request.strip_stop_sequences {
+            completion.strip_stop_sequences(&request.sampling.stop);
+        }
+        if let (Some(cache), Some(key)) = (&request.cache, cache_key) {
+            cache.insert(key, completion.clone());
+        }
+        Ok(completion)
+    }
+
+    /// Removes any of the `stop` sequences from the end of each choice's text content,
+    /// for providers which include the matched stop sequence in the output.
+    pub fn strip_stop_sequences(&mut self, stop: &[String]) {
+        for choice in self.choices.iter_mut() {
+            if let Some(Content::Text(text)) = choice.message.content.as_mut() {
+                if let Some(sequence) = stop
+                    .iter()
+                    .find(|sequence| !sequence.is_empty() && text.ends_with(sequence.as_str()))
+                {
+                    text.truncate(text.len() - sequence.len());
+                }
+            }
+        }
+    }
+
+    /// Retrieves a chat completion stored with `store(true)`.
+    pub async fn fetch(id: &str, credentials: Credentials) -> ApiResponseOrError<Self> {
+        openai_get(&format!("chat/completions/{id}"), Some(credentials)).await
+    }
+
+    /// Deletes a chat completion stored with `store(true)`.
+    pub async fn delete(
+        id: &str,
+        credentials: Credentials,
+    ) -> ApiResponseOrError<DeletedChatCompletion> {
+        openai_delete(&format!("chat/completions/{id}"), Some(credentials)).await
+    }
+
+    /// Replaces the metadata of a chat completion stored with `store(true)`.
+    pub async fn update_metadata(
+        id: &str,
+        metadata: HashMap<String, String>,
+        credentials: Credentials,
+    ) -> ApiResponseOrError<Self> {
+        openai_post(
+            &format!("chat/completions/{id}"),
+            &json!({ "metadata": metadata }),
+            Some(credentials),
+        )
+        .await
+    }
+
+    /// Same as [`create`](Self::create), sent through `client` as with [`Credentials::with_client`].
+    pub async fn create_with_client(
+        mut request: ChatCompletionRequest,
+        client: &Client,
+    ) -> ApiResponseOrError<Self> {
+        request.credentials = Some(credentials_with_client(request.credentials, client));
+        Self::create(request).await
+    }
+
+    /// Streams a chat completion and merges it into a full completion.
+    /// If the stream fails midway, the completion accumulated so far is
+    /// returned alongside the error.
+    pub async fn create_stream_resilient(
+        mut request: ChatCompletionRequest,
+    ) -> Result<Self, ChatCompletionStreamError> {
+        request.remap_max_tokens();
+        let credentials_opt = request.credentials.clone();
+        let mut stream = openai_request_stream(
+            Method::POST,
+            "chat/completions",
+            |r| r.json(&request),
+            credentials_opt,
+        )
+        .await
+        .map_err(|error| ChatCompletionStreamError {
+            partial: None,
+            error: error.into(),
+        })?;
+        let mut merged: Option<ChatCompletionDelta> = None;
+        let result = merge_chat_response_stream(&mut stream, &mut merged).await;
+        stream.close();
+        let completion = merged.map(|merged| {
+            let mut completion: Self = merged.into();
+            if request.strip_stop_sequences {
+                completion.strip_stop_sequences(&request.sampling.stop);
+            }
+            completion
+        });
+        match (result, completion) {
+            (Ok(()), Some(completion)) => Ok(completion),
+            (Ok(()), None) => Err(ChatCompletionStreamError {
+                partial: None,
+                error: anyhow::anyhow!("Stream ended without any chat completion chunks"),
+            }),
+            (Err(error), partial) => Err(ChatCompletionStreamError { partial, error }),
+        }
+    }
+
+    /// Compares only the semantically meaningful fields of two completions,
+    /// the model and the choices, ignoring volatile ones such as `id`, `created` and `usage`.
+    pub fn content_eq(&self, other: &Self) -> bool {
+        self.model == other.model && self.choices == other.choices
+    }
+
+    /// The messages of all choices, in order. With `n` greater than one there is one per choice.
+    pub fn messages(&self) -> Vec<&ChatCompletionMessage> {
+        self.choices.iter().map(|choice| &choice.message).collect()
+    }
+
+    /// The message of the first choice, or `None` if the response has no choices.
+    pub fn first_message(&self) -> Option<&ChatCompletionMessage> {
+        self.choices.first().map(|choice| &choice.message)
+    }
+
+    /// All tool calls requested across the messages of every choice, in order.
+    pub fn all_tool_calls(&self) -> Vec<&ToolCall> {
+        self.choices
+            .iter()
+            .flat_map(|choice| choice.message.tool_calls.iter())
+            .collect()
+    }
+
+    /// Selects the choice with the highest average token log probability,
+    /// for reranking the choices of a request with `n` and `logprobs` set.
+    /// Choices without content log probabilities are skipped.
+    pub fn best_by_logprob(&self) -> Option<&ChatCompletionChoice> {
+        self.choices
+            .iter()
+            .filter_map(|choice| {
+                let tokens = choice.logprobs.as_ref()?.content.as_ref()?;
+                if tokens.is_empty() {
+                    return None;
+                }
+                let average =
+                    tokens.iter().map(|token| token.logprob).sum::<f64>() / tokens.len() as f64;
+                Some((choice, average))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(choice, _)| choice)
+    }
+}
+
+impl ChatCompletionDelta {
+    pub async fn create(
         request: ChatCompletionRequest,
     ) -> Result<Receiver<Self>, CannotCloneRequestError> {
+        let (rx, _abort_handle) = Self::create_cancellable(request).await?;
+        Ok(rx)
+    }
+
+    /// Same as [`create`](Self::create), but also returns a handle that stops the stream.
+    /// Aborting it closes the underlying connection, after which the receiver yields the
+    /// deltas already buffered and then `None`.
+    pub async fn create_cancellable(
+        mut request: ChatCompletionRequest,
+    ) -> Result<(Receiver<Self>, AbortHandle), CannotCloneRequestError> {
+        request.remap_max_tokens();
         let credentials_opt = request.credentials.clone();
         let stream = openai_request_stream(
             Method::POST,
@@ -325,8 +1341,8 @@ impl ChatCompletionDelta {
         )
         .await?;
         let (tx, rx) = channel::<Self>(32);
-        tokio::spawn(forward_deserialized_chat_response_stream(stream, tx));
-        Ok(rx)
+        let task = tokio::spawn(forward_deserialized_chat_response_stream(stream, tx));
+        Ok((rx, task.abort_handle()))
     }
 
     /// Merges the input delta completion into `self`.
@@ -338,13 +1354,28 @@ impl ChatCompletionDelta {
             return Err(ChatCompletionDeltaMergeError::DifferentCompletionIds);
         }
         for other_choice in other.choices.iter() {
-            for choice in self.choices.iter_mut() {
-                if choice.index != other_choice.index {
-                    continue;
-                }
-                choice.merge(other_choice)?;
+            match self
+                .choices
+                .iter_mut()
+                .find(|choice| choice.index == other_choice.index)
+            {
+                Some(choice) => choice.merge(other_choice)?,
+                // With `n > 1`, a choice may first appear in a later delta.
+                None => self.choices.push(other_choice.clone()),
             }
         }
+        self.choices.sort_by_key(|choice| choice.index);
+        // Usage is only sent in the final chunk, when requested via `stream_options`.
+        if other.usage.is_some() {
+            self.usage = other.usage;
+        }
+        if other.service_tier.is_some() {
+            self.service_tier = other.service_tier;
+        }
+        if self.system_fingerprint.is_none() {
+            self.system_fingerprint = other.system_fingerprint;
+        }
+        self.extra.extend(other.extra);
         Ok(())
     }
 }
@@ -390,6 +1421,16 @@ impl ChatCompletionChoiceDelta {
                 }
             }
         };
+        // Merge refusals, which are streamed like contents.
+        if let Some(other_refusal) = &other.delta.refusal {
+            self.delta
+                .refusal
+                .get_or_insert_with(String::new)
+                .push_str(other_refusal);
+        }
+        self.delta
+            .annotations
+            .extend(other.delta.annotations.iter().cloned());
 
         // merge function calls
         // function call names are concatenated
@@ -422,6 +1463,39 @@ impl ChatCompletionChoiceDelta {
                 }
             }
         };
+        // Merge tool calls, whose fragments are matched by their index.
+        for other_tool_call in other.delta.tool_calls.iter() {
+            match self
+                .delta
+                .tool_calls
+                .iter_mut()
+                .find(|tool_call| tool_call.index == other_tool_call.index)
+            {
+                Some(tool_call) => tool_call.merge(other_tool_call),
+                None => self.delta.tool_calls.push(other_tool_call.clone()),
+            }
+        }
+        self.delta
+            .tool_calls
+            .sort_by_key(|tool_call| tool_call.index);
+        // The finish reason is only sent in the last delta of a choice.
+        if other.finish_reason.is_some() {
+            self.finish_reason = other.finish_reason.clone();
+        }
+        // Merge log probabilities.
+        if let Some(other_logprobs) = &other.logprobs {
+            let logprobs = self.logprobs.get_or_insert_with(Default::default);
+            for (tokens, other_tokens) in [
+                (&mut logprobs.content, &other_logprobs.content),
+                (&mut logprobs.refusal, &other_logprobs.refusal),
+            ] {
+                if let Some(other_tokens) = other_tokens {
+                    tokens
+                        .get_or_insert_with(Vec::new)
+                        .extend(other_tokens.iter().cloned());
+                }
+            }
+        }
         Ok(())
     }
 }
@@ -434,23 +1508,35 @@ impl From<ChatCompletionDelta> for ChatCompletion {
             created: delta.created,
             model: delta.model,
             usage: delta.usage,
+            service_tier: delta.service_tier,
+            system_fingerprint: delta.system_fingerprint,
+            extra: delta.extra,
             choices: delta
                 .choices
                 .iter()
                 .map(|choice| ChatCompletionChoice {
                     index: choice.index,
-                    finish_reason: clone_default_unwrapped_option_string(&choice.finish_reason),
+                    finish_reason: choice.finish_reason.clone(),
                     message: ChatCompletionMessage {
                         role: choice
                             .delta
                             .role
                             .unwrap_or_else(|| ChatCompletionMessageRole::System),
-                        content: choice.delta.content.clone(),
+                        content: choice.delta.content.clone().map(Content::Text),
                         name: choice.delta.name.clone(),
                         function_call: choice.delta.function_call.clone().map(|f| f.into()),
                         tool_call_id: None,
-                        tool_calls: Vec::new(),
+                        refusal: choice.delta.refusal.clone(),
+                        annotations: choice.delta.annotations.clone(),
+                        tool_calls: choice
+                            .delta
+                            .tool_calls
+                            .iter()
+                            .cloned()
+                            .map(ToolCall::from)
+                            .collect(),
                     },
+                    logprobs: choice.logprobs.clone(),
                 })
                 .collect(),
         }
@@ -491,13 +1577,43 @@ impl std::fmt::Display for ChatCompletionDeltaMergeError {
 
 impl std::error::Error for ChatCompletionDeltaMergeError {}
 
+/// An error that interrupted a chat completion stream.
+#[derive(Debug)]
+pub struct ChatCompletionStreamError {
+    /// The completion merged from the chunks received before the error, if any.
+    pub partial: Option<ChatCompletion>,
+    pub error: anyhow::Error,
+}
+
+impl std::fmt::Display for ChatCompletionStreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Chat completion stream failed: {}", self.error)
+    }
+}
+
+impl std::error::Error for ChatCompletionStreamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
 async fn forward_deserialized_chat_response_stream(
     mut stream: EventSource,
     tx: Sender<ChatCompletionDelta>,
 ) -> anyhow::Result<()> {
-    while let Some(event) = stream.next().await {
-        let event = event?;
-        match event {
+    // The event source buffers incomplete UTF-8 sequences between network reads,
+    // so a multi-byte character split across chunks is only decoded once complete.
+    loop {
+        let event = tokio::select! {
+            event = stream.next() => event,
+            // Stop as soon as the receiver is dropped, even while waiting for the next event,
+            // so that the connection is closed instead of generating tokens nobody reads.
+            _ = tx.closed() => break,
+        };
+        let Some(event) = event else {
+            break;
+        };
+        match event? {
             Event::Message(event) => {
                 let completion = serde_json::from_str::<ChatCompletionDelta>(&event.data)?;
                 tx.send(completion).await?;
@@ -505,26 +1621,231 @@ async fn forward_deserialized_chat_response_stream(
             _ => {}
         }
     }
+    stream.close();
+    Ok(())
+}
+
+/// Merges the deltas of a chat completion stream into `merged` until the stream is done.
+async fn merge_chat_response_stream(
+    stream: &mut EventSource,
+    merged: &mut Option<ChatCompletionDelta>,
+) -> anyhow::Result<()> {
+    while let Some(event) = stream.next().await {
+        match event? {
+            Event::Message(event) if event.data == "[DONE]" => return Ok(()),
+            Event::Message(event) => {
+                let delta = serde_json::from_str::<ChatCompletionDelta>(&event.data)?;
+                match merged.as_mut() {
+                    Some(merged) => merged.merge(delta)?,
+                    None => *merged = Some(delta),
+                }
+            }
+            Event::Open => {}
+        }
+    }
     Ok(())
 }
 
+sampling_setters!(ChatCompletionBuilder);
+
 impl ChatCompletionBuilder {
+    /// Sets the number of most likely tokens to return at each token position,
+    /// and enables `logprobs`, which the API requires for this, unless it was explicitly disabled.
+    pub fn top_logprobs(mut self, top_logprobs: u8) -> Self {
+        self.top_logprobs = Some(Some(top_logprobs));
+        self.logprobs.get_or_insert(Some(true));
+        self
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(Some(0)) = self.n {
+            return Err("n must be at least 1".to_string());
+        }
+        if let Some(sampling) = &self.sampling {
+            sampling.validate()?;
+        }
+        if let (Some(Some(top_logprobs)), Some(Some(false))) = (self.top_logprobs, self.logprobs) {
+            return Err(format!(
+                "top_logprobs ({top_logprobs}) requires logprobs to be true, but it was set to false"
+            ));
+        }
+        Ok(())
+    }
+
     pub async fn create(self) -> ApiResponseOrError<ChatCompletion> {
-        ChatCompletion::create(self.build().unwrap()).await
+        ChatCompletion::create(self.build().map_err(OpenAiError::invalid_request)?).await
+    }
+
+    /// Requires the output to match a JSON schema built at runtime, enforced strictly.
+    pub fn json_schema_value(self, name: impl Into<String>, schema: Value) -> Self {
+        self.response_format(ChatCompletionResponseFormat::json_schema(
+            name, schema, true,
+        ))
+    }
+
+    pub async fn create_with_client(self, client: &Client) -> ApiResponseOrError<ChatCompletion> {
+        let request = self.build().map_err(OpenAiError::invalid_request)?;
+        ChatCompletion::create_with_client(request, client).await
+    }
+
+    /// Continues the conversation from a completion of this request,
+    /// appending the assistant's reply from the first choice followed by `message`.
+    /// All other settings, such as the model, temperature and credentials, are kept.
+    pub fn follow_up(
+        mut self,
+        completion: &ChatCompletion,
+        message: ChatCompletionMessage,
+    ) -> Self {
+        let messages = self.messages.get_or_insert_with(Vec::new);
+        if let Some(choice) = completion.choices.first() {
+            messages.push(choice.message.clone());
+        }
+        messages.push(message);
+        self
     }
 
-    pub async fn create_stream(
+    pub async fn create_stream(mut self) -> ApiResponseOrError<Receiver<ChatCompletionDelta>> {
+        self.stream = Some(Some(true));
+        let request = self.build().map_err(OpenAiError::invalid_request)?;
+        Ok(ChatCompletionDelta::create(request).await?)
+    }
+
+    /// Streams the completion like [`create_stream`](Self::create_stream), also returning
+    /// a handle to cancel the stream early.
+    pub async fn create_stream_cancellable(
+        mut self,
+    ) -> ApiResponseOrError<(Receiver<ChatCompletionDelta>, AbortHandle)> {
+        self.stream = Some(Some(true));
+        let request = self.build().map_err(OpenAiError::invalid_request)?;
+        Ok(ChatCompletionDelta::create_cancellable(request).await?)
+    }
+
+    /// Streams the completion like [`create_stream`](Self::create_stream), merging the deltas.
+    /// On a stream error, the partial completion received so far is kept in the returned error.
+    pub async fn create_stream_resilient(
         mut self,
-    ) -> Result<Receiver<ChatCompletionDelta>, CannotCloneRequestError> {
+    ) -> Result<ChatCompletion, ChatCompletionStreamError> {
         self.stream = Some(Some(true));
-        ChatCompletionDelta::create(self.build().unwrap()).await
+        let request = self.build().map_err(|error| ChatCompletionStreamError {
+            partial: None,
+            error: OpenAiError::invalid_request(error).into(),
+        })?;
+        ChatCompletion::create_stream_resilient(request).await
     }
 }
 
-fn clone_default_unwrapped_option_string(string: &Option<String>) -> String {
-    match string {
-        Some(value) => value.clone(),
-        None => "".to_string(),
+/// A chat conversation that can be compressed once it grows too long,
+/// by summarizing its older messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversation {
+    pub messages: Vec<ChatCompletionMessage>,
+    /// The estimated number of tokens above which [`compress`](Self::compress) summarizes the conversation.
+    pub token_threshold: usize,
+}
+
+impl Conversation {
+    pub fn new(messages: impl Into<Vec<ChatCompletionMessage>>, token_threshold: usize) -> Self {
+        Conversation {
+            messages: messages.into(),
+            token_threshold,
+        }
+    }
+
+    /// A rough estimate of the number of tokens in the conversation,
+    /// assuming about four characters per token.
+    pub fn estimated_tokens(&self) -> usize {
+        self.messages
+            .iter()
+            .map(|message| {
+                let characters = message
+                    .content
+                    .as_ref()
+                    .map_or(0, |content| content.text().chars().count());
+                // Each message also carries a few tokens of formatting overhead.
+                characters.div_ceil(4) + 4
+            })
+            .sum()
+    }
+
+    /// If the conversation exceeds the token threshold, replaces the messages between
+    /// the leading system or developer prompt and the last `keep_last_n` messages with a
+    /// single summary, generated with `model`.
+    ///
+    /// Returns whether the conversation was compressed.
+    pub async fn compress(
+        &mut self,
+        credentials: Credentials,
+        model: &str,
+        keep_last_n: usize,
+    ) -> ApiResponseOrError<bool> {
+        if self.estimated_tokens() <= self.token_threshold {
+            return Ok(false);
+        }
+        let start = self
+            .messages
+            .iter()
+            .take_while(|message| {
+                matches!(
+                    message.role,
+                    ChatCompletionMessageRole::System | ChatCompletionMessageRole::Developer
+                )
+            })
+            .count();
+        let end = self.messages.len().saturating_sub(keep_last_n).max(start);
+        if end - start < 2 {
+            // There is nothing worth summarizing.
+            return Ok(false);
+        }
+        let transcript = self.messages[start..end]
+            .iter()
+            .map(|message| {
+                let content = message
+                    .content
+                    .as_ref()
+                    .map(Content::text)
+                    .unwrap_or_default();
+                format!("{:?}: {content}", message.role)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let summary = ChatCompletion::builder(
+            model,
+            [
+                ChatCompletionMessage {
+                    role: ChatCompletionMessageRole::System,
+                    content: Some(Content::new_str(
+                        "Summarize the following conversation concisely, \
+                        keeping all facts, decisions and open questions.",
+                    )),
+                    ..Default::default()
+                },
+                ChatCompletionMessage {
+                    role: ChatCompletionMessageRole::User,
+                    content: Some(Content::new_str(transcript)),
+                    ..Default::default()
+                },
+            ],
+        )
+        .credentials(credentials)
+        .create()
+        .await?;
+        let summary = summary
+            .choices
+            .first()
+            .and_then(|choice| choice.message.content.as_ref())
+            .map(Content::text)
+            .unwrap_or_default();
+        self.messages.splice(
+            start..end,
+            [ChatCompletionMessage {
+                role: ChatCompletionMessageRole::System,
+                content: Some(Content::new_str(format!(
+                    "Summary of the earlier conversation: {summary}"
+                ))),
+                ..Default::default()
+            }],
+        );
+        Ok(true)
     }
 }
 
@@ -552,11 +1873,13 @@ mod tests {
             "gpt-3.5-turbo",
             [ChatCompletionMessage {
                 role: ChatCompletionMessageRole::User,
-                content: Some("Hello!".to_string()),
+                content: Some(Content::new_str("Hello!")),
                 name: None,
                 function_call: None,
                 tool_call_id: None,
                 tool_calls: Vec::new(),
+                refusal: None,
+                annotations: Vec::new(),
             }],
         )
         .temperature(0.0)
@@ -590,14 +1913,15 @@ mod tests {
             "gpt-3.5-turbo",
             [ChatCompletionMessage {
                 role: ChatCompletionMessageRole::User,
-                content: Some(
-                    "What type of seed does Mr. England sow in the song? Reply with 1 word."
-                        .to_string(),
-                ),
+                content: Some(Content::new_str(
+                    "What type of seed does Mr. England sow in the song? Reply with 1 word.",
+                )),
                 name: None,
                 function_call: None,
                 tool_call_id: None,
                 tool_calls: Vec::new(),
+                refusal: None,
+                annotations: Vec::new(),
             }],
         )
         // Determinism currently comes from temperature 0, not seed.
@@ -630,11 +1954,13 @@ mod tests {
             "gpt-3.5-turbo",
             [ChatCompletionMessage {
                 role: ChatCompletionMessageRole::User,
-                content: Some("Hello!".to_string()),
+                content: Some(Content::new_str("Hello!")),
                 name: None,
                 function_call: None,
                 tool_call_id: None,
                 tool_calls: Vec::new(),
+                refusal: None,
+                annotations: Vec::new(),
             }],
         )
         .temperature(0.0)
@@ -668,11 +1994,13 @@ mod tests {
             [
                 ChatCompletionMessage {
                     role: ChatCompletionMessageRole::User,
-                    content: Some("What is the weather in Boston?".to_string()),
+                    content: Some(Content::new_str("What is the weather in Boston?")),
                     name: None,
                     function_call: None,
                     tool_call_id: None,
                     tool_calls: Vec::new(),
+                    refusal: None,
+                    annotations: Vec::new(),
                 }
             ]
         ).functions([ChatCompletionFunctionDefinition {
@@ -737,11 +2065,15 @@ mod tests {
             "gpt-3.5-turbo",
             [ChatCompletionMessage {
                 role: ChatCompletionMessageRole::User,
-                content: Some("Write an example JSON for a JWT header using RS256".to_string()),
+                content: Some(Content::new_str(
+                    "Write an example JSON for a JWT header using RS256",
+                )),
                 name: None,
                 function_call: None,
                 tool_call_id: None,
                 tool_calls: Vec::new(),
+                refusal: None,
+                annotations: Vec::new(),
             }],
         )
         .temperature(0.0)
@@ -764,7 +2096,7 @@ mod tests {
             alg: String,
             typ: String,
         }
-        let response = serde_json::from_str::<Response>(response_string).unwrap();
+        let response = serde_json::from_str::<Response>(&response_string.text()).unwrap();
         assert_eq!(
             response,
             Response {
@@ -789,6 +2121,1692 @@ mod tests {
         assert_ne!(builder_c, builder_d);
     }
 
+    #[test]
+    fn content_eq_ignores_volatile_fields() {
+        let completion = |id: &str, created: u64, content: &str| -> ChatCompletion {
+            let mut body = completion_json(
+                "gpt-4o-mini",
+                json!([{
+                    "index": 0,
+                    "finish_reason": "stop",
+                    "message": { "role": "assistant", "content": content },
+                }]),
+            );
+            body["id"] = id.into();
+            body["created"] = created.into();
+            serde_json::from_value(body).unwrap()
+        };
+        let a = completion("chatcmpl-a", 1694268190, "Hello!");
+        let b = completion("chatcmpl-b", 1694268999, "Hello!");
+        let c = completion("chatcmpl-a", 1694268190, "Goodbye!");
+
+        assert_ne!(a, b);
+        assert!(a.content_eq(&b));
+        assert!(!a.content_eq(&c));
+    }
+
+    #[test]
+    fn follow_up_keeps_settings() {
+        let user_message = |content: &str| ChatCompletionMessage {
+            role: ChatCompletionMessageRole::User,
+            content: Some(Content::new_str(content)),
+            ..Default::default()
+        };
+        let builder = ChatCompletion::builder("gpt-4o", [user_message("Hello!")])
+            .temperature(0.5)
+            .seed(65u64);
+        let completion: ChatCompletion = serde_json::from_value(completion_json(
+            "gpt-4o",
+            json!([{
+                "index": 0,
+                "finish_reason": "stop",
+                "message": { "role": "assistant", "content": "Hi! How can I help?" },
+            }]),
+        ))
+        .unwrap();
+
+        let request = builder
+            .follow_up(&completion, user_message("Tell me a joke."))
+            .build()
+            .unwrap();
+
+        assert_eq!(request.model, "gpt-4o");
+        assert_eq!(request.sampling.temperature, Some(0.5));
+        assert_eq!(request.seed, Some(65));
+        let roles: Vec<_> = request.messages.iter().map(|m| m.role).collect();
+        assert_eq!(
+            roles,
+            [
+                ChatCompletionMessageRole::User,
+                ChatCompletionMessageRole::Assistant,
+                ChatCompletionMessageRole::User,
+            ]
+        );
+        assert_eq!(
+            request.messages[1].content.as_ref().unwrap(),
+            "Hi! How can I help?"
+        );
+    }
+
+    #[test]
+    fn role_round_trip() {
+        for (role, name) in [
+            (ChatCompletionMessageRole::System, "system"),
+            (ChatCompletionMessageRole::Developer, "developer"),
+            (ChatCompletionMessageRole::User, "user"),
+            (ChatCompletionMessageRole::Assistant, "assistant"),
+            (ChatCompletionMessageRole::Function, "function"),
+            (ChatCompletionMessageRole::Tool, "tool"),
+        ] {
+            let json = serde_json::to_value(role).unwrap();
+            assert_eq!(json, name);
+            assert_eq!(
+                serde_json::from_value::<ChatCompletionMessageRole>(json).unwrap(),
+                role
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_role() {
+        let error = serde_json::from_str::<ChatCompletionMessage>(
+            r#"{"role": "narrator", "content": "Once upon a time"}"#,
+        )
+        .unwrap_err();
+
+        assert!(error.to_string().contains("unknown variant `narrator`"));
+    }
+
+    #[test]
+    fn choice_null_finish_reason() {
+        let choice: ChatCompletionChoice = serde_json::from_value(serde_json::json!({
+            "index": 0,
+            "finish_reason": null,
+            "message": { "role": "assistant", "content": "Hello!" },
+        }))
+        .unwrap();
+
+        assert_eq!(choice.finish_reason, None);
+    }
+
+    #[test]
+    fn finish_reason() {
+        let reasons: Vec<FinishReason> =
+            serde_json::from_value(json!(["length", "tool_calls", "eos"])).unwrap();
+
+        assert_eq!(
+            reasons,
+            [
+                FinishReason::Length,
+                FinishReason::ToolCalls,
+                FinishReason::Other("eos".to_string()),
+            ]
+        );
+        assert_eq!(reasons[2].as_str(), "eos");
+    }
+
+    #[test]
+    fn top_logprobs_enables_logprobs() {
+        let request = ChatCompletion::builder("gpt-4o", [])
+            .top_logprobs(2)
+            .build()
+            .unwrap();
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["logprobs"], true);
+        assert_eq!(json["top_logprobs"], 2);
+
+        let error = ChatCompletion::builder("gpt-4o", [])
+            .top_logprobs(2)
+            .logprobs(false)
+            .build()
+            .unwrap_err();
+        assert!(error.to_string().contains("requires logprobs to be true"));
+    }
+
+    #[test]
+    fn stop_and_logit_bias() {
+        let request = ChatCompletion::builder("gpt-4o", [])
+            .stop(vec!["\n\n".to_string()])
+            .logit_bias([("50256".to_string(), -100)])
+            .build()
+            .unwrap();
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["stop"], json!(["\n\n"]));
+        assert_eq!(json["logit_bias"], json!({ "50256": -100 }));
+
+        let error = ChatCompletion::builder("gpt-4o", [])
+            .stop(["a", "b", "c", "d", "e"].map(String::from))
+            .build()
+            .unwrap_err();
+        assert!(error.to_string().contains("at most 4 stop sequences"));
+
+        let error = ChatCompletion::builder("gpt-4o", [])
+            .logit_bias([("50256".to_string(), 101)])
+            .build()
+            .unwrap_err();
+        assert!(error.to_string().contains(
+            "logit_bias values must be between -100 and 100, but token 50256 was set to 101"
+        ));
+    }
+
+    #[test]
+    fn request_hash_ignores_credentials() {
+        let hash = |request: &ChatCompletionRequest| {
+            let mut hasher = DefaultHasher::new();
+            request.hash(&mut hasher);
+            hasher.finish()
+        };
+        let metadata = (0..8).map(|i| (format!("key-{i}"), i.to_string()));
+        let request = |api_key: &str| {
+            ChatCompletion::builder("gpt-4o", [])
+                .temperature(0.0)
+                .metadata(metadata.clone().collect::<HashMap<_, _>>())
+                .credentials(Credentials::new(api_key, ""))
+                .build()
+                .unwrap()
+        };
+
+        let a = request("sk-a");
+        let b = request("sk-b");
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+
+        let c = ChatCompletion::builder("gpt-4o", [])
+            .temperature(0.5)
+            .metadata(metadata.collect::<HashMap<_, _>>())
+            .build()
+            .unwrap();
+        assert_ne!(a, c);
+        assert_ne!(hash(&a), hash(&c));
+    }
+
+    #[test]
+    fn default_model() {
+        let credentials = Credentials::new("sk-test", "").with_default_model("gpt-4.1");
+        let request = ChatCompletion::builder_with_default_model([])
+            .credentials(credentials.clone())
+            .build()
+            .unwrap();
+        assert_eq!(request.model, "gpt-4.1");
+
+        let request = ChatCompletion::builder("o3-mini", [])
+            .credentials(credentials)
+            .build()
+            .unwrap();
+        assert_eq!(request.model, "o3-mini");
+    }
+
+    #[tokio::test]
+    async fn default_model_missing() {
+        // The environment variable is a fallback for credentials without a default model.
+        if std::env::var_os("OPENAI_DEFAULT_MODEL").is_some() {
+            return;
+        }
+        let error = ChatCompletion::builder_with_default_model([])
+            .credentials(Credentials::new("sk-test", ""))
+            .create()
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_type, "invalid_request");
+        assert!(error.message.contains("OPENAI_DEFAULT_MODEL"));
+    }
+
+    #[test]
+    fn sampling_out_of_range() {
+        let error = ChatCompletion::builder("gpt-4o", [])
+            .temperature(5.0)
+            .build()
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("temperature must be between 0 and 2, but it was set to 5"));
+
+        let error = ChatCompletion::builder("gpt-4o", [])
+            .frequency_penalty(-2.5)
+            .build()
+            .unwrap_err();
+        assert!(error.to_string().contains("frequency_penalty"));
+
+        ChatCompletion::builder("gpt-4o", [])
+            .temperature(2.0)
+            .top_p(0.0)
+            .presence_penalty(-2.0)
+            .build()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn sampling_out_of_range_create() {
+        let error = ChatCompletion::builder("gpt-4o", [])
+            .temperature(5.0)
+            .create()
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_type, "invalid_request");
+        assert!(error.message.contains("temperature"));
+
+        let error = ChatCompletionDelta::builder("gpt-4o", [])
+            .top_p(-0.5)
+            .create_stream()
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_type, "invalid_request");
+
+        let error = ChatCompletion::builder("gpt-4o", [])
+            .presence_penalty(3.0)
+            .create_stream_resilient()
+            .await
+            .unwrap_err();
+        assert!(error.partial.is_none());
+        assert!(error.to_string().contains("presence_penalty"));
+    }
+
+    #[tokio::test]
+    async fn zero_choices_rejected() {
+        let error = ChatCompletion::builder("gpt-4o", [])
+            .n(0)
+            .create()
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_type, "invalid_request");
+        assert!(error.message.contains("n must be at least 1"));
+    }
+
+    #[test]
+    fn choice_logprobs() {
+        let choice: ChatCompletionChoice = serde_json::from_value(serde_json::json!({
+            "index": 0,
+            "message": { "role": "assistant", "content": "Hello!" },
+            "logprobs": {
+                "content": [
+                    {
+                        "token": "Hello",
+                        "logprob": -0.31725305,
+                        "bytes": [72, 101, 108, 108, 111],
+                        "top_logprobs": [
+                            { "token": "Hello", "logprob": -0.31725305, "bytes": [72, 101, 108, 108, 111] },
+                            { "token": "Hi", "logprob": -1.3190403, "bytes": [72, 105] },
+                        ],
+                    },
+                    { "token": "!", "logprob": -0.02380986, "bytes": [33], "top_logprobs": [] },
+                ],
+                "refusal": null,
+            },
+            "finish_reason": "stop",
+        }))
+        .unwrap();
+
+        let tokens = choice.logprobs.unwrap().content.unwrap();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token, "Hello");
+        assert_eq!(tokens[0].bytes.as_deref(), Some("Hello".as_bytes()));
+        assert_eq!(tokens[0].top_logprobs[1].token, "Hi");
+        assert_eq!(tokens[1].logprob, -0.02380986);
+    }
+
+    #[tokio::test]
+    async fn create_with_client() {
+        let body = completion_body(
+            "gpt-4o-mini",
+            json!([{
+                "index": 0,
+                "message": { "role": "assistant", "content": "Hello!" },
+                "finish_reason": "stop",
+            }]),
+        );
+        let (credentials, server) = crate::tests::mock_server_connections(2, 200, &body).await;
+        let client = Client::builder().user_agent("shared-pool").build().unwrap();
+
+        for _ in 0..2 {
+            let completion = ChatCompletion::builder("gpt-4o-mini", [])
+                .credentials(credentials.clone())
+                .create_with_client(&client)
+                .await
+                .unwrap();
+            assert_eq!(
+                completion
+                    .first_message()
+                    .unwrap()
+                    .content
+                    .as_ref()
+                    .unwrap(),
+                "Hello!"
+            );
+        }
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|request| {
+            let request = request.to_lowercase();
+            request.contains("user-agent: shared-pool")
+                && request.contains("authorization: bearer sk-mock")
+        }));
+    }
+
+    #[test]
+    fn all_tool_calls() {
+        let tool_call = |id: &str, name: &str| json!({ "id": id, "type": "function", "function": { "name": name, "arguments": "{}" } });
+        let completion: ChatCompletion = serde_json::from_value(completion_json(
+            "gpt-4o-mini",
+            json!([
+                {
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [tool_call("call_a", "get_weather"), tool_call("call_b", "get_time")],
+                    },
+                    "finish_reason": "tool_calls",
+                },
+                {
+                    "index": 1,
+                    "message": { "role": "assistant", "content": "It is sunny." },
+                    "finish_reason": "stop",
+                },
+            ]),
+        ))
+        .unwrap();
+
+        let ids = completion
+            .all_tool_calls()
+            .iter()
+            .map(|tool_call| tool_call.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["call_a", "call_b"]);
+    }
+
+    #[test]
+    fn best_choice_by_logprob() {
+        let choice = |index: u64, content: &str, logprobs: &[f64]| {
+            let tokens = logprobs
+                .iter()
+                .map(|logprob| json!({ "token": "x", "logprob": logprob, "bytes": null, "top_logprobs": [] }))
+                .collect::<Vec<_>>();
+            json!({
+                "index": index,
+                "message": { "role": "assistant", "content": content },
+                "logprobs": { "content": tokens, "refusal": null },
+                "finish_reason": "stop",
+            })
+        };
+        let completion: ChatCompletion = serde_json::from_value(completion_json(
+            "gpt-4o-mini",
+            json!([
+                choice(0, "Crabs walk sideways.", &[-0.1, -2.5, -0.9]),
+                choice(1, "Crabs can walk sideways.", &[-0.2, -0.4, -0.1, -0.3]),
+            ]),
+        ))
+        .unwrap();
+
+        let best = completion.best_by_logprob().unwrap();
+        assert_eq!(best.index, 1);
+    }
+
+    #[test]
+    fn merge_logprobs() {
+        let chunk = |content: &str, logprob: f64| -> ChatCompletionDelta {
+            serde_json::from_value(chunk_json(
+                json!([{
+                    "index": 0,
+                    "delta": { "content": content },
+                    "logprobs": {
+                        "content": [{ "token": content, "logprob": logprob, "bytes": null, "top_logprobs": [] }],
+                        "refusal": null,
+                    },
+                    "finish_reason": null,
+                }]),
+                Value::Null,
+            ))
+            .unwrap()
+        };
+        let mut merged = delta(0, Some("assistant"), "");
+        merged.merge(chunk("Hello", -0.5)).unwrap();
+        merged.merge(chunk(" there", -0.25)).unwrap();
+        let completion = ChatCompletion::from(merged);
+
+        let tokens = completion.choices[0]
+            .logprobs
+            .as_ref()
+            .unwrap()
+            .content
+            .as_ref()
+            .unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|token| (token.token.as_str(), token.logprob))
+                .collect::<Vec<_>>(),
+            [("Hello", -0.5), (" there", -0.25)]
+        );
+    }
+
+    fn delta(index: u64, role: Option<&str>, content: &str) -> ChatCompletionDelta {
+        let mut delta = serde_json::json!({ "content": content });
+        if let Some(role) = role {
+            delta["role"] = role.into();
+        }
+        serde_json::from_value(chunk_json(
+            json!([{ "index": index, "delta": delta, "finish_reason": null }]),
+            Value::Null,
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn merge_multiple_choices() {
+        let mut merged = delta(0, Some("assistant"), "");
+        for chunk in [
+            delta(1, Some("assistant"), ""),
+            delta(1, None, "Good"),
+            delta(0, None, "Hello"),
+            delta(1, None, "bye"),
+            delta(0, None, " there"),
+        ] {
+            merged.merge(chunk).unwrap();
+        }
+        let completion = ChatCompletion::from(merged);
+
+        assert_eq!(completion.choices.len(), 2);
+        assert_eq!(completion.choices[0].index, 0);
+        assert_eq!(
+            completion.choices[0].message.content.as_ref().unwrap(),
+            "Hello there"
+        );
+        assert_eq!(completion.choices[1].index, 1);
+        assert_eq!(
+            completion.choices[1].message.content.as_ref().unwrap(),
+            "Goodbye"
+        );
+        assert_eq!(
+            completion.choices[1].message.role,
+            ChatCompletionMessageRole::Assistant
+        );
+    }
+
+    #[tokio::test]
+    async fn compress_long_conversation() {
+        let message = |role, content: String| ChatCompletionMessage {
+            role,
+            content: Some(Content::new_str(content)),
+            ..Default::default()
+        };
+        let mut messages = vec![message(
+            ChatCompletionMessageRole::System,
+            "You are a helpful assistant.".to_string(),
+        )];
+        for turn in 0..20 {
+            messages.push(message(
+                ChatCompletionMessageRole::User,
+                format!("Tell me fact number {turn} about crabs."),
+            ));
+            messages.push(message(
+                ChatCompletionMessageRole::Assistant,
+                format!("Crab fact number {turn}: crabs walk sideways. ").repeat(5),
+            ));
+        }
+        let mut conversation = Conversation::new(messages, 500);
+        assert!(conversation.estimated_tokens() > 500);
+
+        let (credentials, server) = crate::tests::mock_server(
+            200,
+            &completion_body(
+                "gpt-4o-mini",
+                json!([{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "The user asked for crab facts." },
+                    "finish_reason": "stop",
+                }]),
+            ),
+        )
+        .await;
+        let compressed = conversation
+            .compress(credentials, "gpt-4o-mini", 4)
+            .await
+            .unwrap();
+
+        assert!(compressed);
+        // The system prompt, the summary and the last four messages.
+        assert_eq!(conversation.messages.len(), 6);
+        assert_eq!(
+            conversation.messages[0].content.as_ref().unwrap(),
+            "You are a helpful assistant."
+        );
+        assert_eq!(
+            conversation.messages[1].content.as_ref().unwrap(),
+            "Summary of the earlier conversation: The user asked for crab facts."
+        );
+        assert_eq!(
+            conversation.messages[2].content.as_ref().unwrap(),
+            "Tell me fact number 18 about crabs."
+        );
+        assert!(server.await.unwrap().contains("Crab fact number 0"));
+    }
+
+    #[test]
+    fn merge_parallel_tool_calls() {
+        let chunk = |tool_call: Value| -> ChatCompletionDelta {
+            serde_json::from_value(chunk_json(
+                json!([{ "index": 0, "delta": { "tool_calls": [tool_call] }, "finish_reason": null }]),
+                Value::Null,
+            ))
+            .unwrap()
+        };
+        let mut merged = delta(0, Some("assistant"), "");
+        for tool_call in [
+            json!({ "index": 0, "id": "call_a", "type": "function", "function": { "name": "get_weather", "arguments": "" } }),
+            json!({ "index": 1, "id": "call_b", "type": "function", "function": { "name": "get_time", "arguments": "" } }),
+            json!({ "index": 0, "function": { "arguments": "{\"city\":" } }),
+            json!({ "index": 1, "function": { "arguments": "{\"zone\":\"UTC\"}" } }),
+            json!({ "index": 0, "function": { "arguments": "\"Paris\"}" } }),
+        ] {
+            merged.merge(chunk(tool_call)).unwrap();
+        }
+        let completion = ChatCompletion::from(merged);
+
+        assert_eq!(
+            completion.choices[0].message.tool_calls,
+            [
+                ToolCall {
+                    id: "call_a".to_string(),
+                    r#type: "function".to_string(),
+                    function: ToolCallFunction {
+                        name: "get_weather".to_string(),
+                        arguments: r#"{"city":"Paris"}"#.to_string(),
+                    },
+                },
+                ToolCall {
+                    id: "call_b".to_string(),
+                    r#type: "function".to_string(),
+                    function: ToolCallFunction {
+                        name: "get_time".to_string(),
+                        arguments: r#"{"zone":"UTC"}"#.to_string(),
+                    },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_keeps_first_role() {
+        let mut merged = delta(0, Some("assistant"), "Hello");
+        for role in ["developer", "tool", "system", "user"] {
+            merged.merge(delta(0, Some(role), "!")).unwrap();
+        }
+        merged.merge(delta(0, None, "?")).unwrap();
+        let completion = ChatCompletion::from(merged);
+
+        assert_eq!(
+            completion.choices[0].message.role,
+            ChatCompletionMessageRole::Assistant
+        );
+        assert_eq!(
+            completion.choices[0].message.content.as_ref().unwrap(),
+            "Hello!!!!?"
+        );
+    }
+
+    #[test]
+    fn merge_empty_choices() {
+        let empty = || -> ChatCompletionDelta {
+            serde_json::from_value(chunk_json(json!([]), Value::Null)).unwrap()
+        };
+        let mut merged = empty();
+        merged.merge(delta(0, Some("assistant"), "Hello")).unwrap();
+        merged.merge(empty()).unwrap();
+        merged.merge(delta(0, None, "!")).unwrap();
+        let completion = ChatCompletion::from(merged);
+
+        assert_eq!(completion.choices.len(), 1);
+        assert_eq!(
+            completion.choices[0].message.content.as_ref().unwrap(),
+            "Hello!"
+        );
+    }
+
+    #[test]
+    fn merge_choices_out_of_order() {
+        let mut merged = delta(1, Some("assistant"), "B");
+        merged.merge(delta(0, Some("assistant"), "A")).unwrap();
+        let completion = ChatCompletion::from(merged);
+
+        let indices: Vec<u64> = completion.choices.iter().map(|c| c.index).collect();
+        assert_eq!(indices, [0, 1]);
+        assert_eq!(completion.choices[0].message.content.as_ref().unwrap(), "A");
+    }
+
+    #[test]
+    fn unknown_response_fields() {
+        let mut body = completion_json(
+            "llama-3.1-8b-instant",
+            json!([{
+                "index": 0,
+                "finish_reason": "stop",
+                "message": { "role": "assistant", "content": "Hi!" },
+            }]),
+        );
+        body["system_fingerprint"] = "fp_9cb648b966".into();
+        body["service_tier"] = "on_demand".into();
+        body["x_groq"] = json!({ "id": "req_01jbd6g2qdfw2adyrt2az8hz4w" });
+        let completion: ChatCompletion = serde_json::from_value(body).unwrap();
+
+        assert_eq!(
+            completion.system_fingerprint.as_deref(),
+            Some("fp_9cb648b966")
+        );
+        assert_eq!(
+            completion.extra["x_groq"]["id"],
+            "req_01jbd6g2qdfw2adyrt2az8hz4w"
+        );
+        assert!(!completion.extra.contains_key("choices"));
+        assert_eq!(completion.service_tier.as_deref(), Some("on_demand"));
+        assert!(!completion.extra.contains_key("service_tier"));
+    }
+
+    #[test]
+    fn built_in_tool_serialization() {
+        assert_eq!(
+            serde_json::to_value(ChatCompletionTool::FileSearch).unwrap(),
+            json!({ "type": "file_search" })
+        );
+        assert_eq!(
+            serde_json::to_value(ChatCompletionTool::CodeInterpreter).unwrap(),
+            json!({ "type": "code_interpreter" })
+        );
+        let tool: ChatCompletionTool =
+            serde_json::from_value(json!({ "type": "code_interpreter" })).unwrap();
+        assert_eq!(tool, ChatCompletionTool::CodeInterpreter);
+    }
+
+    #[test]
+    fn web_search_tool() {
+        assert_eq!(
+            serde_json::to_value(ChatCompletionTool::web_search()).unwrap(),
+            json!({ "type": "web_search_preview" })
+        );
+        let tool = ChatCompletionTool::WebSearchPreview {
+            search_context_size: Some(SearchContextSize::Low),
+        };
+        assert_eq!(
+            serde_json::to_value(tool).unwrap(),
+            json!({ "type": "web_search_preview", "search_context_size": "low" })
+        );
+
+        let message: ChatCompletionMessage = serde_json::from_value(json!({
+            "role": "assistant",
+            "content": "Rust 1.0 was released in 2015.",
+            "annotations": [
+                {
+                    "type": "url_citation",
+                    "url_citation": {
+                        "start_index": 0,
+                        "end_index": 30,
+                        "url": "https://blog.rust-lang.org/2015/05/15/Rust-1.0.html",
+                        "title": "Announcing Rust 1.0",
+                    },
+                },
+                { "type": "file_citation", "file_id": "file-123" },
+            ],
+        }))
+        .unwrap();
+        let Annotation::UrlCitation { url_citation } = &message.annotations[0] else {
+            panic!("expected a URL citation");
+        };
+        assert_eq!(url_citation.title, "Announcing Rust 1.0");
+        assert_eq!(url_citation.end_index, 30);
+        assert_eq!(message.annotations[1], Annotation::Unknown);
+    }
+
+    #[test]
+    fn tool_choice_serialization() {
+        let request = ChatCompletion::builder("gpt-4o", [])
+            .tools([ChatCompletionTool::function(
+                ChatCompletionFunctionDefinition {
+                    name: "get_weather".to_string(),
+                    description: None,
+                    parameters: None,
+                },
+            )])
+            .tool_choice(ToolChoice::AllowedTools(vec![
+                "get_weather".to_string(),
+                "get_time".to_string(),
+            ]))
+            .build()
+            .unwrap();
+        let value = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(
+            value["tools"],
+            json!([{ "type": "function", "function": { "name": "get_weather" } }])
+        );
+        assert_eq!(
+            value["tool_choice"],
+            json!({
+                "type": "allowed_tools",
+                "allowed_tools": {
+                    "mode": "auto",
+                    "tools": [
+                        { "type": "function", "function": { "name": "get_weather" } },
+                        { "type": "function", "function": { "name": "get_time" } },
+                    ],
+                },
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(ToolChoice::Required).unwrap(),
+            "required"
+        );
+        assert_eq!(
+            serde_json::to_value(ToolChoice::Function("get_weather".to_string())).unwrap(),
+            json!({ "type": "function", "function": { "name": "get_weather" } })
+        );
+    }
+
+    #[test]
+    fn cache_controlled_content_part() {
+        let content = Content::new_cached_str("You are a helpful assistant.");
+        assert_eq!(
+            serde_json::to_value(&content).unwrap(),
+            json!([{
+                "type": "text",
+                "text": "You are a helpful assistant.",
+                "cache_control": { "type": "ephemeral" },
+            }])
+        );
+        assert_eq!(
+            serde_json::to_value(ContentPart::image_url("https://example.com/crab.png")).unwrap(),
+            json!({ "type": "image_url", "image_url": { "url": "https://example.com/crab.png" } })
+        );
+    }
+
+    #[test]
+    fn reasoning_effort_serialization() {
+        let request = ChatCompletion::builder("o3-mini", [])
+            .reasoning_effort(ReasoningEffort::High)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["reasoning_effort"],
+            "high"
+        );
+    }
+
+    #[test]
+    fn request_serialization_unset() {
+        let request = ChatCompletion::builder(
+            "gpt-4o",
+            [ChatCompletionMessage {
+                role: ChatCompletionMessageRole::User,
+                content: Some(Content::new_str("Hello!")),
+                ..Default::default()
+            }],
+        )
+        .build()
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "model": "gpt-4o",
+                "messages": [{ "role": "user", "content": "Hello!" }],
+            })
+        );
+    }
+
+    #[test]
+    fn request_serialization_all_fields() {
+        let function = ChatCompletionFunctionDefinition {
+            name: "get_weather".to_string(),
+            description: Some("Gets the weather in a city".to_string()),
+            parameters: Some(json!({
+                "type": "object",
+                "properties": { "city": { "type": "string" } },
+            })),
+        };
+        let request = ChatCompletion::builder(
+            "gpt-4o",
+            [ChatCompletionMessage {
+                role: ChatCompletionMessageRole::User,
+                content: Some(Content::new_str("Hello!")),
+                ..Default::default()
+            }],
+        )
+        .temperature(0.5)
+        .top_p(0.25)
+        .presence_penalty(0.25)
+        .frequency_penalty(-0.5)
+        .stop(vec!["\n".to_string()])
+        .logit_bias(HashMap::from([("50256".to_string(), -100)]))
+        .n(2)
+        .stream(true)
+        .stream_options(StreamOptions {
+            include_usage: true,
+        })
+        .strip_stop_sequences(true)
+        .seed(42u64)
+        .reasoning_effort(ReasoningEffort::Low)
+        .max_tokens(256u64)
+        .max_completion_tokens(512u64)
+        .top_logprobs(3)
+        .user("user-123")
+        .functions([function.clone()])
+        .function_call(json!("auto"))
+        .tools([ChatCompletionTool::function(function)])
+        .tool_choice(ToolChoice::Required)
+        .response_format(ChatCompletionResponseFormat::json_object())
+        .store(true)
+        .metadata(HashMap::from([("project".to_string(), "crab".to_string())]))
+        .service_tier(ServiceTier::Flex)
+        .prediction("fn main() {}")
+        .venice_parameters(VeniceParameters {
+            include_venice_system_prompt: false,
+        })
+        .credentials(Credentials::new("sk-test", ""))
+        .cache(ChatCompletionCache::new(1))
+        .build()
+        .unwrap();
+        let parameters = json!({
+            "type": "object",
+            "properties": { "city": { "type": "string" } },
+        });
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "model": "gpt-4o",
+                "messages": [{ "role": "user", "content": "Hello!" }],
+                "temperature": 0.5,
+                "top_p": 0.25,
+                "presence_penalty": 0.25,
+                "frequency_penalty": -0.5,
+                "stop": ["\n"],
+                "logit_bias": { "50256": -100 },
+                "n": 2,
+                "stream": true,
+                "stream_options": { "include_usage": true },
+                "seed": 42,
+                "reasoning_effort": "low",
+                "max_tokens": 256,
+                "max_completion_tokens": 512,
+                "logprobs": true,
+                "top_logprobs": 3,
+                "user": "user-123",
+                "functions": [{
+                    "name": "get_weather",
+                    "description": "Gets the weather in a city",
+                    "parameters": parameters,
+                }],
+                "function_call": "auto",
+                "tools": [{
+                    "type": "function",
+                    "function": {
+                        "name": "get_weather",
+                        "description": "Gets the weather in a city",
+                        "parameters": parameters,
+                    },
+                }],
+                "tool_choice": "required",
+                "response_format": { "type": "json_object" },
+                "store": true,
+                "metadata": { "project": "crab" },
+                "service_tier": "flex",
+                "prediction": { "type": "content", "content": "fn main() {}" },
+                "venice_parameters": { "include_venice_system_prompt": false },
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn json_schema_structured_output() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Weather {
+            city: String,
+            celsius: i32,
+        }
+
+        let (credentials, server) = crate::tests::mock_server(
+            200,
+            &completion_body(
+                "gpt-4o-mini",
+                json!([{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": r#"{"city":"Lisbon","celsius":21}"#,
+                    },
+                    "finish_reason": "stop",
+                }]),
+            ),
+        )
+        .await;
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "city": { "type": "string" },
+                "celsius": { "type": "integer" },
+            },
+            "required": ["city", "celsius"],
+            "additionalProperties": false,
+        });
+
+        let completion = ChatCompletion::builder("gpt-4o-mini", [])
+            .json_schema_value("weather", schema.clone())
+            .credentials(credentials)
+            .create()
+            .await
+            .unwrap();
+
+        let weather: Weather = completion.choices[0].parsed().unwrap();
+        assert_eq!(
+            weather,
+            Weather {
+                city: "Lisbon".to_string(),
+                celsius: 21,
+            }
+        );
+        let request = server.await.unwrap();
+        let body: Value =
+            serde_json::from_str(&request[request.find("\r\n\r\n").unwrap()..]).unwrap();
+        assert_eq!(
+            body["response_format"],
+            json!({
+                "type": "json_schema",
+                "json_schema": { "name": "weather", "schema": schema, "strict": true },
+            })
+        );
+    }
+
+    #[test]
+    fn parsed_refusal() {
+        let choice: ChatCompletionChoice = serde_json::from_value(json!({
+            "index": 0,
+            "message": {
+                "role": "assistant",
+                "content": null,
+                "refusal": "I'm sorry, I can't help with that.",
+            },
+            "finish_reason": "stop",
+        }))
+        .unwrap();
+
+        let error = choice.parsed::<Value>().unwrap_err();
+        assert!(matches!(
+            error,
+            StructuredOutputError::Refusal(refusal) if refusal == "I'm sorry, I can't help with that."
+        ));
+    }
+
+    #[tokio::test]
+    async fn reasoning_model_max_tokens() {
+        let (credentials, server) =
+            crate::tests::mock_server(200, &completion_body("o1-mini", json!([]))).await;
+        ChatCompletion::builder("o1-mini", [])
+            .max_tokens(256u64)
+            .credentials(credentials)
+            .create()
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.contains(r#""max_completion_tokens":256"#));
+        assert!(!request.contains("\"max_tokens\""));
+
+        for model in [
+            "o1",
+            "o3-mini",
+            "o4-mini-2025-04-16",
+            "gpt-5",
+            "ft:o4-mini:org::abc",
+        ] {
+            assert!(is_reasoning_model(model), "{model}");
+        }
+        for model in ["gpt-4o", "omni-moderation-latest", "llama-3.1-8b"] {
+            assert!(!is_reasoning_model(model), "{model}");
+        }
+    }
+
+    #[test]
+    fn max_completion_tokens_serialization() {
+        let request = ChatCompletion::builder("o3-mini", [])
+            .max_completion_tokens(1024u32)
+            .build()
+            .unwrap();
+        let value = serde_json::to_value(&request).unwrap();
+
+        assert_eq!(value["max_completion_tokens"], 1024);
+        assert!(value.get("max_tokens").is_none());
+    }
+
+    #[tokio::test]
+    async fn image_from_path() {
+        let content = Content::image_from_path("test_data/pixel.png", Some(ImageDetail::Low))
+            .await
+            .unwrap();
+        let Content::Parts(parts) = content else {
+            panic!("expected content parts");
+        };
+        let ContentPart::ImageUrl { image_url, .. } = &parts[0] else {
+            panic!("expected an image part");
+        };
+        let data = image_url
+            .url
+            .strip_prefix("data:image/png;base64,")
+            .unwrap();
+        assert_eq!(
+            BASE64_STANDARD.decode(data).unwrap(),
+            std::fs::read("test_data/pixel.png").unwrap()
+        );
+        assert_eq!(image_url.detail, Some(ImageDetail::Low));
+
+        let error = Content::image_from_path("test_data/file_upload_test1.jsonl", None)
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_type, "invalid_image");
+    }
+
+    #[test]
+    fn content_parts_round_trip() {
+        let text = Content::new_str("Hello!");
+        assert_eq!(serde_json::to_value(&text).unwrap(), "Hello!");
+
+        let parts = Content::new_parts(vec![
+            ContentPart::text("What's in this image?"),
+            ContentPart::image_url_with_detail("https://example.com/crab.png", ImageDetail::Low),
+        ]);
+        let value = serde_json::to_value(&parts).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!([
+                { "type": "text", "text": "What's in this image?" },
+                {
+                    "type": "image_url",
+                    "image_url": { "url": "https://example.com/crab.png", "detail": "low" },
+                },
+            ])
+        );
+        assert_eq!(serde_json::from_value::<Content>(value).unwrap(), parts);
+        assert_eq!(parts.text(), "What's in this image?");
+
+        assert_eq!(
+            serde_json::to_value(Content::new_image_url("https://example.com/crab.png")).unwrap(),
+            serde_json::json!([
+                { "type": "image_url", "image_url": { "url": "https://example.com/crab.png" } },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_multi_byte_characters_split_across_chunks() {
+        let content = |content: &str| {
+            chunk(
+                json!([{ "index": 0, "delta": { "role": "assistant", "content": content }, "finish_reason": null }]),
+                Value::Null,
+            )
+        };
+        let mut body = content("Café ");
+        body.extend(content("☕ and 🥐"));
+        body.extend(b"data: [DONE]\n\n");
+        // Split the raw bytes in the middle of every multi-byte character.
+        let text = String::from_utf8(body.clone()).unwrap();
+        let mut splits = vec![0];
+        for (position, character) in text.char_indices() {
+            if character.len_utf8() > 1 {
+                splits.push(position + 1);
+            }
+        }
+        splits.push(body.len());
+        let chunks = splits
+            .windows(2)
+            .map(|window| body[window[0]..window[1]].to_vec())
+            .collect();
+        let (credentials, _server) = crate::tests::mock_event_stream(chunks).await;
+
+        let chat_stream = ChatCompletion::builder("gpt-4o-mini", [])
+            .credentials(credentials)
+            .create_stream()
+            .await
+            .unwrap();
+        let chat_completion = stream_to_completion(chat_stream).await;
+
+        assert_eq!(
+            chat_completion.choices[0].message.content.as_ref().unwrap(),
+            "Café ☕ and 🥐"
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_refusal() {
+        let refusal = |delta: Value| {
+            chunk(
+                json!([{ "index": 0, "delta": delta, "finish_reason": null }]),
+                Value::Null,
+            )
+        };
+        let chunks = vec![
+            refusal(json!({ "role": "assistant", "content": null, "refusal": "" })),
+            refusal(json!({ "refusal": "I'm sorry," })),
+            refusal(json!({ "refusal": " I can't help with that." })),
+            b"data: [DONE]\n\n".to_vec(),
+        ];
+        let (credentials, _server) = crate::tests::mock_event_stream(chunks).await;
+
+        let completion = ChatCompletion::builder("gpt-4o-mini", [])
+            .credentials(credentials)
+            .create_stream_resilient()
+            .await
+            .unwrap();
+
+        let message = completion.first_message().unwrap();
+        assert_eq!(message.content, None);
+        assert_eq!(
+            message.refusal.as_deref(),
+            Some("I'm sorry, I can't help with that.")
+        );
+    }
+
+    #[tokio::test]
+    async fn dropped_receiver_closes_stream() {
+        let (credentials, server) = crate::tests::mock_stalled_event_stream(vec![chunk(
+            json!([{ "index": 0, "delta": { "role": "assistant", "content": "Hi" } }]),
+            Value::Null,
+        )])
+        .await;
+
+        let mut chat_stream = ChatCompletion::builder("gpt-4o-mini", [])
+            .credentials(credentials)
+            .create_stream()
+            .await
+            .unwrap();
+        assert!(chat_stream.recv().await.is_some());
+        drop(chat_stream);
+
+        // The server never ends the stream, so it only returns once the client disconnects.
+        tokio::time::timeout(std::time::Duration::from_secs(5), server)
+            .await
+            .expect("the stream was not closed after dropping the receiver")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn stream_include_usage() {
+        let chunks = vec![
+            chunk(
+                json!([{ "index": 0, "delta": { "role": "assistant", "content": "Hi" }, "finish_reason": null }]),
+                Value::Null,
+            ),
+            chunk(
+                json!([{ "index": 0, "delta": {}, "finish_reason": "stop" }]),
+                Value::Null,
+            ),
+            chunk(
+                json!([]),
+                json!({ "prompt_tokens": 9, "completion_tokens": 1, "total_tokens": 10 }),
+            ),
+            b"data: [DONE]\n\n".to_vec(),
+        ];
+        let (credentials, server) = crate::tests::mock_event_stream(chunks).await;
+
+        let chat_stream = ChatCompletion::builder("gpt-4o-mini", [])
+            .stream_options(StreamOptions {
+                include_usage: true,
+            })
+            .credentials(credentials)
+            .create_stream()
+            .await
+            .unwrap();
+        let chat_completion = stream_to_completion(chat_stream).await;
+
+        assert_eq!(
+            chat_completion.choices[0].message.content.as_ref().unwrap(),
+            "Hi"
+        );
+        let usage = chat_completion.usage.unwrap();
+        assert_eq!(usage.prompt_tokens, 9);
+        assert_eq!(usage.total_tokens, 10);
+        let request = server.await.unwrap();
+        assert!(request.contains(r#""stream_options":{"include_usage":true}"#));
+    }
+
+    /// Replays a recorded stream from `test_data/chat_streams`, one event per write,
+    /// and returns the merged completion.
+    async fn merge_fixture(name: &str) -> ChatCompletion {
+        let fixture =
+            std::fs::read_to_string(format!("test_data/chat_streams/{name}.sse")).unwrap();
+        let events = fixture
+            .split_inclusive("\n\n")
+            .map(|event| event.as_bytes().to_vec())
+            .collect();
+        let (credentials, _server) = crate::tests::mock_event_stream(events).await;
+
+        ChatCompletion::builder("gpt-4o-mini", [])
+            .credentials(credentials)
+            .create_stream_resilient()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn merge_content_fixture() {
+        let completion = merge_fixture("content").await;
+
+        assert_eq!(completion.id, "chatcmpl-AYbGcCq0EkZ8ds5zNv0p2WUxkLmQ2");
+        assert_eq!(completion.choices.len(), 1);
+        let choice = &completion.choices[0];
+        assert_eq!(choice.message.role, ChatCompletionMessageRole::Assistant);
+        assert_eq!(choice.message.content.as_ref().unwrap(), "1, 2, 3");
+        assert_eq!(choice.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(
+            completion.system_fingerprint.as_deref(),
+            Some("fp_0705bf87c0")
+        );
+        assert_eq!(completion.service_tier, None);
+        assert_eq!(completion.usage, None);
+    }
+
+    #[tokio::test]
+    async fn merge_multiple_choices_fixture() {
+        let completion = merge_fixture("multiple_choices").await;
+
+        let contents: Vec<_> = completion
+            .messages()
+            .iter()
+            .map(|message| message.content.as_ref().unwrap().text())
+            .collect();
+        assert_eq!(contents, ["Hello!", "Hi there"]);
+        assert_eq!(
+            completion
+                .first_message()
+                .unwrap()
+                .content
+                .as_ref()
+                .unwrap(),
+            "Hello!"
+        );
+        assert!(completion
+            .choices
+            .iter()
+            .all(|choice| choice.finish_reason == Some(FinishReason::Stop)));
+    }
+
+    #[tokio::test]
+    async fn merge_function_call_fixture() {
+        let completion = merge_fixture("function_call").await;
+
+        let choice = &completion.choices[0];
+        assert_eq!(choice.message.content, None);
+        assert_eq!(choice.finish_reason, Some(FinishReason::FunctionCall));
+        let function_call = choice.message.function_call.as_ref().unwrap();
+        assert_eq!(function_call.name, "get_current_weather");
+        assert_eq!(
+            serde_json::from_str::<Value>(&function_call.arguments).unwrap(),
+            json!({ "location": "Boston, MA" })
+        );
+    }
+
+    #[tokio::test]
+    async fn merge_parallel_tool_calls_fixture() {
+        let completion = merge_fixture("parallel_tool_calls").await;
+
+        let choice = &completion.choices[0];
+        assert_eq!(choice.finish_reason, Some(FinishReason::ToolCalls));
+        let calls: Vec<_> = choice
+            .message
+            .tool_calls
+            .iter()
+            .map(|call| {
+                (
+                    call.id.as_str(),
+                    call.function.name.as_str(),
+                    serde_json::from_str::<Value>(&call.function.arguments).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                (
+                    "call_Vx1Xg9Gk1oC2f7kXKPbYkzTz",
+                    "get_weather",
+                    json!({ "city": "Paris" })
+                ),
+                (
+                    "call_3dQvUu7hWQ0bGQx8c1TOWfRa",
+                    "get_weather",
+                    json!({ "city": "Tokyo" })
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn merge_usage_fixture() {
+        let completion = merge_fixture("usage").await;
+
+        assert_eq!(completion.choices.len(), 1);
+        assert_eq!(
+            completion.choices[0].message.content.as_ref().unwrap(),
+            "Hello!"
+        );
+        let usage = completion.usage.unwrap();
+        assert_eq!(
+            (
+                usage.prompt_tokens,
+                usage.completion_tokens,
+                usage.total_tokens
+            ),
+            (9, 2, 11)
+        );
+        assert_eq!(
+            usage.completion_tokens_details.unwrap().reasoning_tokens,
+            Some(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_resilient_keeps_partial_completion() {
+        let content = |content: &str| {
+            chunk(
+                json!([{ "index": 0, "delta": { "role": "assistant", "content": content }, "finish_reason": null }]),
+                Value::Null,
+            )
+        };
+        // The connection is closed before `[DONE]` is sent.
+        let (credentials, _server) =
+            crate::tests::mock_event_stream(vec![content("Once upon"), content(" a time")]).await;
+
+        let error = ChatCompletion::builder("gpt-4o-mini", [])
+            .credentials(credentials)
+            .create_stream_resilient()
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            error.partial.unwrap().choices[0]
+                .message
+                .content
+                .as_ref()
+                .unwrap(),
+            "Once upon a time"
+        );
+
+        let mut body = content("The end");
+        body.extend(b"data: [DONE]\n\n");
+        let (credentials, _server) = crate::tests::mock_event_stream(vec![body]).await;
+        let completion = ChatCompletion::builder("gpt-4o-mini", [])
+            .credentials(credentials)
+            .create_stream_resilient()
+            .await
+            .unwrap();
+        assert_eq!(
+            completion.choices[0].message.content.as_ref().unwrap(),
+            "The end"
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_cancel() {
+        let chunks = (0..50)
+            .map(|_| {
+                chunk(
+                    json!([{ "index": 0, "delta": { "content": "crab " }, "finish_reason": null }]),
+                    Value::Null,
+                )
+            })
+            .collect();
+        let (credentials, server) = crate::tests::mock_event_stream(chunks).await;
+
+        let (mut chat_stream, abort_handle) = ChatCompletion::builder("gpt-4o-mini", [])
+            .credentials(credentials)
+            .create_stream_cancellable()
+            .await
+            .unwrap();
+        chat_stream.recv().await.unwrap();
+        abort_handle.abort();
+        let mut received = 1;
+        while chat_stream.recv().await.is_some() {
+            received += 1;
+        }
+
+        assert!(received < 50);
+        // The server stops writing once the connection is closed.
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn list_stored_completions() {
+        let (credentials, server) = crate::tests::mock_server(
+            200,
+            &json!({
+                "object": "list",
+                "data": [completion_json(
+                    "gpt-4o-mini",
+                    json!([{
+                        "index": 0,
+                        "message": { "role": "assistant", "content": "Hello!" },
+                        "finish_reason": "stop",
+                    }]),
+                )],
+                "first_id": "chatcmpl-123",
+                "last_id": "chatcmpl-123",
+                "has_more": true,
+            })
+            .to_string(),
+        )
+        .await;
+
+        let completions = ChatCompletions::builder()
+            .model("gpt-4o-mini")
+            .metadata([("project".to_string(), "crabs".to_string())])
+            .pagination(RequestPagination {
+                limit: Some(1),
+                ..Default::default()
+            })
+            .credentials(credentials)
+            .list()
+            .await
+            .unwrap();
+
+        assert!(completions.has_more);
+        assert_eq!(completions.last_id.as_deref(), Some("chatcmpl-123"));
+        assert_eq!(
+            completions.data[0].choices[0]
+                .message
+                .content
+                .as_ref()
+                .unwrap(),
+            "Hello!"
+        );
+        let request = server.await.unwrap();
+        assert!(request.starts_with(
+            "GET /v1/chat/completions?limit=1&model=gpt-4o-mini&metadata%5Bproject%5D=crabs "
+        ));
+    }
+
+    #[tokio::test]
+    async fn strip_stop_sequences() {
+        let (credentials, server) = crate::tests::mock_server(
+            200,
+            &completion_body(
+                "llama-3.1-8b",
+                json!([{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "1, 2, 3, 4" },
+                    "finish_reason": "stop",
+                }]),
+            ),
+        )
+        .await;
+        let completion = ChatCompletion::builder("llama-3.1-8b", [])
+            .stop(vec![", 5".to_string(), ", 4".to_string()])
+            .strip_stop_sequences(true)
+            .credentials(credentials)
+            .create()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            completion.choices[0].message.content.as_ref().unwrap(),
+            "1, 2, 3"
+        );
+        let request = server.await.unwrap();
+        assert!(!request.contains("strip_stop_sequences"));
+    }
+
+    #[tokio::test]
+    async fn strip_stop_sequences_stream_resilient() {
+        let content = |content: &str| {
+            chunk(
+                json!([{ "index": 0, "delta": { "role": "assistant", "content": content }, "finish_reason": null }]),
+                Value::Null,
+            )
+        };
+        let builder = ChatCompletion::builder("llama-3.1-8b", [])
+            .stop(vec![", 4".to_string()])
+            .strip_stop_sequences(true);
+
+        let mut body = content("1, 2, 3");
+        body.extend(content(", 4"));
+        body.extend(b"data: [DONE]\n\n");
+        let (credentials, _server) = crate::tests::mock_event_stream(vec![body]).await;
+        let completion = builder
+            .clone()
+            .credentials(credentials)
+            .create_stream_resilient()
+            .await
+            .unwrap();
+        assert_eq!(
+            completion.choices[0].message.content.as_ref().unwrap(),
+            "1, 2, 3"
+        );
+
+        // The connection is closed before `[DONE]` is sent.
+        let (credentials, _server) =
+            crate::tests::mock_event_stream(vec![content("1, 2, 3"), content(", 4")]).await;
+        let error = builder
+            .credentials(credentials)
+            .create_stream_resilient()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.partial.unwrap().choices[0]
+                .message
+                .content
+                .as_ref()
+                .unwrap(),
+            "1, 2, 3"
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_completion() {
+        let (credentials, server) = crate::tests::mock_server(
+            200,
+            &completion_body(
+                "gpt-4o-mini",
+                json!([{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "Paris" },
+                    "finish_reason": "stop",
+                }]),
+            ),
+        )
+        .await;
+        let cache = ChatCompletionCache::new(8);
+        let builder = ChatCompletion::builder(
+            "gpt-4o-mini",
+            [ChatCompletionMessage {
+                role: ChatCompletionMessageRole::User,
+                content: Some(Content::new_str("What is the capital of France?")),
+                ..Default::default()
+            }],
+        )
+        .temperature(0.0)
+        .cache(cache.clone());
+
+        let first = builder
+            .clone()
+            .credentials(credentials.clone())
+            .create()
+            .await
+            .unwrap();
+        let request = server.await.unwrap();
+        assert!(!request.contains("cache"));
+        // The mock server only answers once, so this must come from the cache.
+        let second = builder.credentials(credentials).create().await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn update_stored_completion_metadata() {
+        let mut body = completion_json("gpt-4o-mini", json!([]));
+        body["metadata"] = json!({ "project": "lobsters" });
+        let (credentials, server) = crate::tests::mock_server(200, &body.to_string()).await;
+        let completion = ChatCompletion::update_metadata(
+            "chatcmpl-123",
+            HashMap::from([("project".to_string(), "lobsters".to_string())]),
+            credentials,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(completion.id, "chatcmpl-123");
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /v1/chat/completions/chatcmpl-123 "));
+        assert!(request.ends_with(r#"{"metadata":{"project":"lobsters"}}"#));
+    }
+
+    #[tokio::test]
+    async fn delete_missing_stored_completion() {
+        let (credentials, server) = crate::tests::mock_server(
+            404,
+            r#"{"error": {"message": "Completion chatcmpl-404 not found", "type": "invalid_request_error", "param": null, "code": "not_found"}}"#,
+        )
+        .await;
+        let error = ChatCompletion::delete("chatcmpl-404", credentials)
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.code.as_deref(), Some("not_found"));
+        assert!(server
+            .await
+            .unwrap()
+            .starts_with("DELETE /v1/chat/completions/chatcmpl-404 "));
+    }
+
+    /// A chat completion response with the given choices.
+    fn completion_json(model: &str, choices: Value) -> Value {
+        json!({
+            "id": "chatcmpl-123",
+            "object": "chat.completion",
+            "created": 1694268190,
+            "model": model,
+            "choices": choices,
+        })
+    }
+
+    fn completion_body(model: &str, choices: Value) -> String {
+        completion_json(model, choices).to_string()
+    }
+
+    /// A streamed chat completion chunk with the given choices and usage.
+    fn chunk_json(choices: Value, usage: Value) -> Value {
+        json!({
+            "id": "chatcmpl-123",
+            "object": "chat.completion.chunk",
+            "created": 1694268190,
+            "model": "gpt-4o-mini",
+            "choices": choices,
+            "usage": usage,
+        })
+    }
+
+    /// Same as [`chunk_json`], as a server-sent event.
+    fn chunk(choices: Value, usage: Value) -> Vec<u8> {
+        format!("data: {}\n\n", chunk_json(choices, usage)).into_bytes()
+    }
+
     async fn stream_to_completion(
         mut chat_stream: Receiver<ChatCompletionDelta>,
     ) -> ChatCompletion {
@@ -814,22 +3832,23 @@ mod tests {
             [
                 ChatCompletionMessage {
                     role: ChatCompletionMessageRole::User,
-                    content: Some(
+                    content: Some(Content::new_str(
                         "What's 0.9102847*28456? \
                         reply in plain text, \
                         round the number to to 2 decimals \
                         and reply with the result number only, \
-                        with no full stop at the end"
-                            .to_string(),
-                    ),
+                        with no full stop at the end",
+                    )),
                     name: None,
                     function_call: None,
                     tool_call_id: None,
                     tool_calls: Vec::new(),
+                    refusal: None,
+                    annotations: Vec::new(),
                 },
                 ChatCompletionMessage {
                     role: ChatCompletionMessageRole::Assistant,
-                    content: Some("Let me calculate that for you.".to_string()),
+                    content: Some(Content::new_str("Let me calculate that for you.")),
                     name: None,
                     function_call: None,
                     tool_call_id: None,
@@ -841,14 +3860,18 @@ mod tests {
                             arguments: "not_required_to_be_valid_here".to_string(),
                         },
                     }],
+                    refusal: None,
+                    annotations: Vec::new(),
                 },
                 ChatCompletionMessage {
                     role: ChatCompletionMessageRole::Tool,
-                    content: Some("the result is 25903.061423199997".to_string()),
+                    content: Some(Content::new_str("the result is 25903.061423199997")),
                     name: None,
                     function_call: None,
                     tool_call_id: Some("the_tool_call".to_owned()),
                     tool_calls: Vec::new(),
+                    refusal: None,
+                    annotations: Vec::new(),
                 },
             ],
         )
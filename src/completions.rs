@@ -1,9 +1,17 @@
 //! Given a prompt, the model will return one or more predicted completions,
 //! and can also return the probabilities of alternative tokens at each position.
-use super::{openai_post, ApiResponseOrError, Credentials, Usage};
+use super::{
+    credentials_with_client, openai_post, openai_request_stream, ApiResponseOrError, Credentials,
+    OpenAiError, SamplingParams, Usage,
+};
 use derive_builder::Builder;
+use futures_util::StreamExt;
+use reqwest::{Client, Method};
+use reqwest_eventsource::{CannotCloneRequestError, Event, EventSource};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
 
 #[derive(Deserialize, Clone)]
 pub struct Completion {
@@ -12,26 +20,110 @@ pub struct Completion {
     pub model: String,
     pub choices: Vec<CompletionChoice>,
     pub usage: Usage,
+    /// Any other fields of the response, such as `system_fingerprint`,
+    /// or fields specific to an OpenAI-compatible provider.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Deserialize, Clone)]
 pub struct CompletionChoice {
     pub text: String,
     pub index: u16,
-    pub logprobs: Option<u16>,
+    pub logprobs: Option<Logprobs>,
     pub finish_reason: String,
 }
 
+/// Log probabilities of the generated tokens, returned when `logprobs` is set on the request.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Logprobs {
+    /// The generated tokens.
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    /// The log probability of each token.
+    /// With `echo`, the first prompt token has no log probability.
+    #[serde(default)]
+    pub token_logprobs: Vec<Option<f64>>,
+    /// The most likely tokens at each position, mapped to their log probability.
+    #[serde(default)]
+    pub top_logprobs: Vec<Option<HashMap<String, f64>>>,
+    /// The character offset of each token in the text.
+    #[serde(default)]
+    pub text_offset: Vec<u32>,
+}
+
+/// A chunk of a completion, received during a response stream.
+/// Chunks can be merged into one with [`CompletionChunk::merge`].
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct CompletionChunk {
+    pub id: String,
+    pub created: u32,
+    pub model: String,
+    #[serde(default)]
+    pub choices: Vec<CompletionChunkChoice>,
+    pub usage: Option<Usage>,
+}
+
+/// Same as CompletionChoice, but received during a response stream.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct CompletionChunkChoice {
+    pub text: String,
+    pub index: u16,
+    pub logprobs: Option<Logprobs>,
+    /// The reason the model stopped generating tokens, only set in the last chunk of a choice.
+    pub finish_reason: Option<String>,
+}
+
+/// One or more prompts to generate completions for.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Prompt {
+    Text(String),
+    /// Several prompts completed in a single request,
+    /// with `n` choices for each prompt, in order.
+    Texts(Vec<String>),
+}
+
+impl From<String> for Prompt {
+    fn from(text: String) -> Self {
+        Prompt::Text(text)
+    }
+}
+
+impl From<&str> for Prompt {
+    fn from(text: &str) -> Self {
+        Prompt::Text(text.to_string())
+    }
+}
+
+impl From<&String> for Prompt {
+    fn from(text: &String) -> Self {
+        Prompt::Text(text.clone())
+    }
+}
+
+impl From<Vec<String>> for Prompt {
+    fn from(texts: Vec<String>) -> Self {
+        Prompt::Texts(texts)
+    }
+}
+
 #[derive(Serialize, Builder, Debug, Clone)]
 #[builder(pattern = "owned")]
 #[builder(name = "CompletionBuilder")]
 #[builder(setter(strip_option, into))]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct CompletionRequest {
     /// ID of the model to use.
     /// You can use the [List models](https://beta.openai.com/docs/api-reference/models/list)
     /// API to see all of your available models,
     /// or see our [Model overview](https://beta.openai.com/docs/models/overview)
     /// for descriptions of them.
+    ///
+    /// Defaults to the default model of the credentials, or the `OPENAI_DEFAULT_MODEL` env variable.
+    #[builder(
+        default = "crate::default_model(self.credentials.as_ref().and_then(Option::as_ref))?"
+    )]
     pub model: String,
     /// The prompt(s) to generate completions for, encoded as a string,
     /// array of strings, array of tokens, or array of token arrays.
@@ -40,7 +132,7 @@ pub struct CompletionRequest {
     /// so if a prompt is not specified the model will generate as if from the beginning of a new document.
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default)]
-    pub prompt: Option<String>,
+    pub prompt: Option<Prompt>,
     /// The suffix that comes after a completion of inserted text.
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default)]
@@ -52,22 +144,6 @@ pub struct CompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(setter(into = false), default)]
     pub max_tokens: Option<u16>,
-    /// What [sampling temperature](https://towardsdatascience.com/how-to-sample-from-language-models-682bceb97277) to use.
-    /// Higher values means the model will take more risks.
-    /// Try 0.9 for more creative applications, and 0 (argmax sampling) for ones with a well-defined answer.
-    ///
-    /// We generally recommend altering this or `top_p` but not both.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[builder(default)]
-    pub temperature: Option<f32>,
-    /// An alternative to sampling with temperature, called nucleus sampling,
-    /// where the model considers the results of the tokens with top_p probability mass.
-    /// So 0.1 means only the tokens comprising the top 10% probability mass are considered.
-    ///
-    /// We generally recommend altering this or `temperature` but not both.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[builder(default)]
-    pub top_p: Option<f32>,
     /// How many completions to generate for each prompt.
     ///
     /// **Note:** Because this parameter generates many completions, it can quickly consume your token quota.
@@ -79,7 +155,7 @@ pub struct CompletionRequest {
     /// [server-sent events](https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events/Using_server-sent_events#Event_stream_format)
     /// as they become available, with the stream terminated by a `data: [DONE]` message.
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[builder(setter(skip), default)] // skipped until properly implemented
+    #[builder(setter(skip), default)] // set by `create_stream`
     pub stream: Option<bool>,
     /// Include the log probabilities on the logprobs most likely tokens, as well the chosen tokens.
     /// For example, if logprobs is 5, the API will return a list of the 5 most likely tokens.
@@ -94,27 +170,6 @@ pub struct CompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default)]
     pub echo: Option<bool>,
-    /// Up to 4 sequences where the API will stop generating further tokens.
-    /// The returned text will not contain the stop sequence.
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    #[builder(default)]
-    pub stop: Vec<String>,
-    /// Number between -2.0 and 2.0.
-    /// Positive values penalize new tokens based on whether they appear in the text so far,
-    /// increasing the model's likelihood to talk about new topics.
-    ///
-    /// [See more information about frequency and presence penalties](https://beta.openai.com/docs/api-reference/parameter-details).
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[builder(default)]
-    pub presence_penalty: Option<f32>,
-    /// Number between -2.0 and 2.0.
-    /// Positive values penalize new tokens based on their existing frequency in the text so far,
-    /// decreasing the model's likelihood to repeat the same line verbatim.
-    ///
-    /// [See more information about frequency and presence penalties](https://beta.openai.com/docs/api-reference/parameter-details).
-    #[serde(skip_serializing_if = "Option::is_none")]
-    #[builder(default)]
-    pub frequency_penalty: Option<f32>,
     /// Generates `best_of` completions server-side and returns the "best" (the one with the highest log probability per token).
     /// Results cannot be streamed.
     ///
@@ -126,23 +181,15 @@ pub struct CompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default)]
     pub best_of: Option<u16>,
-    /// Modify the likelihood of specified tokens appearing in the completion.
-    ///
-    /// Accepts a json object that maps tokens (specified by their token ID in the GPT tokenizer) to an associated bias value from -100 to 100.
-    /// You can use this [tokenizer tool](https://beta.openai.com/tokenizer?view=bpe) (which works for both GPT-2 and GPT-3) to convert text to token IDs.
-    /// Mathematically, the bias is added to the logits generated by the model prior to sampling.
-    /// The exact effect will vary per model, but values between -1 and 1 should decrease or increase likelihood of selection;
-    /// values like -100 or 100 should result in a ban or exclusive selection of the relevant token.
-    ///
-    /// As an example, you can pass `{"50256": -100}` to prevent the <|endoftext|> token from being generated.
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
-    #[builder(default)]
-    pub logit_bias: HashMap<String, i16>,
     /// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
     /// [Learn more](https://beta.openai.com/docs/guides/safety-best-practices/end-user-ids).
     #[serde(skip_serializing_if = "Option::is_none")]
     #[builder(default)]
     pub user: Option<String>,
+    /// Sampling parameters, such as the temperature and stop sequences.
+    #[serde(flatten)]
+    #[builder(default, setter(custom))]
+    pub sampling: SamplingParams,
     /// The credentials to use for this request.
     #[serde(skip_serializing)]
     #[builder(default)]
@@ -156,20 +203,174 @@ impl Completion {
         openai_post("completions", &request, credentials_opt).await
     }
 
+    /// Creates a completion sent through `client`, as with [`Credentials::with_client`].
+    pub async fn create_with_client(
+        mut request: CompletionRequest,
+        client: &Client,
+    ) -> ApiResponseOrError<Self> {
+        request.credentials = Some(credentials_with_client(request.credentials, client));
+        Self::create(request).await
+    }
+
     pub fn builder(model: &str) -> CompletionBuilder {
         CompletionBuilder::create_empty().model(model)
     }
+
+    /// Same as [`builder`](Self::builder), but using the default model.
+    /// See [`Credentials::with_default_model`].
+    pub fn builder_with_default_model() -> CompletionBuilder {
+        CompletionBuilder::create_empty()
+    }
+
+    /// Creates a completion for the provided prompt and parameters, streaming its chunks
+    /// as they are generated.
+    async fn create_stream(
+        mut request: CompletionRequest,
+    ) -> Result<Receiver<CompletionChunk>, CannotCloneRequestError> {
+        request.stream = Some(true);
+        let credentials_opt = request.credentials.clone();
+        let stream = openai_request_stream(
+            Method::POST,
+            "completions",
+            |r| r.json(&request),
+            credentials_opt,
+        )
+        .await?;
+        let (tx, rx) = channel::<CompletionChunk>(32);
+        tokio::spawn(forward_deserialized_completion_stream(stream, tx));
+        Ok(rx)
+    }
+
+    /// The choices generated for the prompt at `prompt_index`,
+    /// for a request with several prompts and `n` choices per prompt (1 by default).
+    pub fn choices_for_prompt(&self, prompt_index: usize, n: usize) -> Vec<&CompletionChoice> {
+        let mut choices: Vec<_> = self
+            .choices
+            .iter()
+            .filter(|choice| choice.index as usize / n.max(1) == prompt_index)
+            .collect();
+        choices.sort_by_key(|choice| choice.index);
+        choices
+    }
+}
+
+sampling_setters!(CompletionBuilder);
+
+impl CompletionChunk {
+    /// Merges a later chunk of the same completion into this one,
+    /// appending the text of each choice.
+    pub fn merge(&mut self, other: CompletionChunk) -> Result<(), CompletionChunkMergeError> {
+        if other.id != self.id {
+            return Err(CompletionChunkMergeError::DifferentCompletionIds);
+        }
+        for other_choice in other.choices {
+            match self
+                .choices
+                .iter_mut()
+                .find(|choice| choice.index == other_choice.index)
+            {
+                Some(choice) => {
+                    choice.text.push_str(&other_choice.text);
+                    if let Some(other_logprobs) = other_choice.logprobs {
+                        let logprobs = choice.logprobs.get_or_insert_with(Default::default);
+                        logprobs.tokens.extend(other_logprobs.tokens);
+                        logprobs
+                            .token_logprobs
+                            .extend(other_logprobs.token_logprobs);
+                        logprobs.top_logprobs.extend(other_logprobs.top_logprobs);
+                        logprobs.text_offset.extend(other_logprobs.text_offset);
+                    }
+                    if other_choice.finish_reason.is_some() {
+                        choice.finish_reason = other_choice.finish_reason;
+                    }
+                }
+                None => self.choices.push(other_choice),
+            }
+        }
+        self.choices.sort_by_key(|choice| choice.index);
+        if other.usage.is_some() {
+            self.usage = other.usage;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum CompletionChunkMergeError {
+    DifferentCompletionIds,
+}
+
+impl std::fmt::Display for CompletionChunkMergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompletionChunkMergeError::DifferentCompletionIds => {
+                f.write_str("Different completion IDs")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompletionChunkMergeError {}
+
+async fn forward_deserialized_completion_stream(
+    mut stream: EventSource,
+    tx: Sender<CompletionChunk>,
+) -> anyhow::Result<()> {
+    loop {
+        let event = tokio::select! {
+            event = stream.next() => event,
+            // Stop as soon as the receiver is dropped, even while waiting for the next event.
+            _ = tx.closed() => break,
+        };
+        let Some(event) = event else {
+            break;
+        };
+        match event? {
+            Event::Message(event) if event.data == "[DONE]" => break,
+            Event::Message(event) => {
+                let chunk = serde_json::from_str::<CompletionChunk>(&event.data)?;
+                tx.send(chunk).await?;
+            }
+            Event::Open => {}
+        }
+    }
+    stream.close();
+    Ok(())
 }
 
 impl CompletionBuilder {
+    fn validate(&self) -> Result<(), String> {
+        match &self.sampling {
+            Some(sampling) => sampling.validate(),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn create_stream(self) -> ApiResponseOrError<Receiver<CompletionChunk>> {
+        let request = self.build().map_err(OpenAiError::invalid_request)?;
+        Ok(Completion::create_stream(request).await?)
+    }
+
+    /// Sets several prompts to complete in a single request.
+    pub fn prompts(mut self, prompts: Vec<String>) -> Self {
+        self.prompt = Some(Some(Prompt::Texts(prompts)));
+        self
+    }
+
     pub async fn create(self) -> ApiResponseOrError<Completion> {
-        Completion::create(self.build().unwrap()).await
+        Completion::create(self.build().map_err(OpenAiError::invalid_request)?).await
+    }
+
+    pub async fn create_with_client(self, client: &Client) -> ApiResponseOrError<Completion> {
+        let request = self.build().map_err(OpenAiError::invalid_request)?;
+        Completion::create_with_client(request, client).await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chat::ChatCompletion;
     use crate::tests::DEFAULT_LEGACY_MODEL;
     use dotenvy::dotenv;
 
@@ -192,4 +393,243 @@ mod tests {
             "\n\nThis is a test."
         );
     }
+
+    #[test]
+    fn logprobs_deserialization() {
+        let completion: Completion = serde_json::from_value(serde_json::json!({
+            "id": "cmpl-123",
+            "object": "text_completion",
+            "created": 1694268190,
+            "model": DEFAULT_LEGACY_MODEL,
+            "choices": [{
+                "text": " a test",
+                "index": 0,
+                "logprobs": {
+                    "tokens": [" a", " test"],
+                    "token_logprobs": [-0.25, -0.5],
+                    "top_logprobs": [{ " a": -0.25, " the": -1.5 }, { " test": -0.5 }],
+                    "text_offset": [7, 9],
+                },
+                "finish_reason": "length",
+            }],
+            "usage": { "prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7 },
+        }))
+        .unwrap();
+
+        let logprobs = completion.choices[0].logprobs.as_ref().unwrap();
+        assert_eq!(logprobs.tokens, [" a", " test"]);
+        assert_eq!(logprobs.token_logprobs, [Some(-0.25), Some(-0.5)]);
+        assert_eq!(logprobs.top_logprobs[0].as_ref().unwrap()[" the"], -1.5);
+        assert_eq!(logprobs.text_offset, [7, 9]);
+    }
+
+    #[tokio::test]
+    async fn multiple_prompts() {
+        let choice = |index: u16, text: &str| serde_json::json!({ "text": text, "index": index, "logprobs": null, "finish_reason": "stop" });
+        let (credentials, server) = crate::tests::mock_server(
+            200,
+            &serde_json::json!({
+                "id": "cmpl-123",
+                "object": "text_completion",
+                "created": 1694268190,
+                "model": DEFAULT_LEGACY_MODEL,
+                "choices": [
+                    choice(2, " Paris"),
+                    choice(0, " 4"),
+                    choice(3, " Paris."),
+                    choice(1, " Four"),
+                ],
+                "usage": { "prompt_tokens": 12, "completion_tokens": 8, "total_tokens": 20 },
+            })
+            .to_string(),
+        )
+        .await;
+
+        let completion = Completion::builder(DEFAULT_LEGACY_MODEL)
+            .prompts(vec![
+                "2 + 2 =".to_string(),
+                "The capital of France is".to_string(),
+            ])
+            .n(2u16)
+            .credentials(credentials)
+            .create()
+            .await
+            .unwrap();
+
+        let texts = |prompt_index| {
+            completion
+                .choices_for_prompt(prompt_index, 2)
+                .iter()
+                .map(|choice| choice.text.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts(0), [" 4", " Four"]);
+        assert_eq!(texts(1), [" Paris", " Paris."]);
+        let request = server.await.unwrap();
+        assert!(request.contains(r#""prompt":["2 + 2 =","The capital of France is"]"#));
+    }
+
+    #[test]
+    fn request_serialization_unset() {
+        let request = Completion::builder(DEFAULT_LEGACY_MODEL).build().unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({ "model": DEFAULT_LEGACY_MODEL })
+        );
+    }
+
+    #[test]
+    fn request_serialization_all_fields() {
+        let request = Completion::builder(DEFAULT_LEGACY_MODEL)
+            .prompt("Say this is a test")
+            .suffix("That was a test.")
+            .max_tokens(16)
+            .temperature(0.5)
+            .top_p(0.25)
+            .presence_penalty(0.5)
+            .frequency_penalty(-0.5)
+            .stop(vec!["\n".to_string()])
+            .logit_bias(HashMap::from([("50256".to_string(), -100)]))
+            .n(2u16)
+            .logprobs(5u8)
+            .echo(true)
+            .best_of(3u16)
+            .user("user-123")
+            .credentials(Credentials::new("sk-test", ""))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "model": DEFAULT_LEGACY_MODEL,
+                "prompt": "Say this is a test",
+                "suffix": "That was a test.",
+                "max_tokens": 16,
+                "temperature": 0.5,
+                "top_p": 0.25,
+                "presence_penalty": 0.5,
+                "frequency_penalty": -0.5,
+                "stop": ["\n"],
+                "logit_bias": { "50256": -100 },
+                "n": 2,
+                "logprobs": 5,
+                "echo": true,
+                "best_of": 3,
+                "user": "user-123",
+            })
+        );
+    }
+
+    #[test]
+    fn sampling_out_of_range() {
+        let error = Completion::builder(DEFAULT_LEGACY_MODEL)
+            .top_p(1.5)
+            .build()
+            .unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("top_p must be between 0 and 1, but it was set to 1.5"));
+    }
+
+    #[tokio::test]
+    async fn sampling_out_of_range_create() {
+        let error = Completion::builder(DEFAULT_LEGACY_MODEL)
+            .temperature(-1.0)
+            .create()
+            .await
+            .err()
+            .expect("out-of-range request should not be sent");
+        assert_eq!(error.error_type, "invalid_request");
+        assert!(error.message.contains("temperature"));
+
+        let error = Completion::builder(DEFAULT_LEGACY_MODEL)
+            .frequency_penalty(2.5)
+            .create_stream()
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_type, "invalid_request");
+    }
+
+    #[test]
+    fn shared_sampling_serialization() {
+        let sampling = SamplingParams {
+            temperature: Some(0.5),
+            top_p: Some(0.9),
+            presence_penalty: Some(0.25),
+            frequency_penalty: Some(-0.5),
+            stop: vec!["\n".to_string()],
+            logit_bias: HashMap::from([("50256".to_string(), -100)]),
+        };
+        let completion_request = Completion::builder(DEFAULT_LEGACY_MODEL)
+            .sampling(sampling.clone())
+            .build()
+            .unwrap();
+        let chat_request = ChatCompletion::builder("gpt-4o", [])
+            .temperature(0.5)
+            .top_p(0.9)
+            .presence_penalty(0.25)
+            .frequency_penalty(-0.5)
+            .stop(vec!["\n".to_string()])
+            .logit_bias(HashMap::from([("50256".to_string(), -100)]))
+            .build()
+            .unwrap();
+
+        let completion_json = serde_json::to_value(completion_request).unwrap();
+        let chat_json = serde_json::to_value(chat_request).unwrap();
+        let expected = serde_json::to_value(&sampling).unwrap();
+        for (key, value) in expected.as_object().unwrap() {
+            assert_eq!(completion_json[key], *value, "{key}");
+            assert_eq!(chat_json[key], *value, "{key}");
+        }
+        assert_eq!(expected["logit_bias"], serde_json::json!({ "50256": -100 }));
+        assert_eq!(expected.as_object().unwrap().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn completion_stream() {
+        let chunk = |text: &str, finish_reason: Value| {
+            format!(
+                "data: {}\n\n",
+                serde_json::json!({
+                    "id": "cmpl-123",
+                    "object": "text_completion",
+                    "created": 1694268190,
+                    "model": DEFAULT_LEGACY_MODEL,
+                    "choices": [{
+                        "text": text,
+                        "index": 0,
+                        "logprobs": null,
+                        "finish_reason": finish_reason,
+                    }],
+                })
+            )
+            .into_bytes()
+        };
+        let chunks = vec![
+            chunk("This is", Value::Null),
+            chunk(" a test", Value::Null),
+            chunk("", Value::from("stop")),
+            b"data: [DONE]\n\n".to_vec(),
+        ];
+        let (credentials, server) = crate::tests::mock_event_stream(chunks).await;
+
+        let mut stream = Completion::builder(DEFAULT_LEGACY_MODEL)
+            .prompt("Say this is a test")
+            .credentials(credentials)
+            .create_stream()
+            .await
+            .unwrap();
+        let mut merged = stream.recv().await.unwrap();
+        while let Some(chunk) = stream.recv().await {
+            merged.merge(chunk).unwrap();
+        }
+
+        assert_eq!(merged.choices[0].text, "This is a test");
+        assert_eq!(merged.choices[0].finish_reason.as_deref(), Some("stop"));
+        let request = server.await.unwrap();
+        assert!(request.contains("\"stream\":true"));
+    }
 }
@@ -3,6 +3,7 @@
 //! Related guide: [Embeddings](https://beta.openai.com/docs/guides/embeddings)
 
 use super::{openai_post, ApiResponseOrError, Credentials};
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Clone)]
@@ -32,6 +33,22 @@ pub struct Embedding {
     pub vec: Vec<f64>,
 }
 
+/// Same as Embeddings, but with single precision vectors,
+/// which use half the memory and are precise enough for similarity search.
+#[derive(Deserialize, Clone)]
+pub struct EmbeddingsF32 {
+    pub data: Vec<EmbeddingF32>,
+    pub model: String,
+    pub usage: EmbeddingsUsage,
+}
+
+/// Same as Embedding, but with a single precision vector.
+#[derive(Deserialize, Clone)]
+pub struct EmbeddingF32 {
+    #[serde(rename = "embedding")]
+    pub vec: Vec<f32>,
+}
+
 impl Embeddings {
     /// Creates an embedding vector representing the input text.
     ///
@@ -61,6 +78,18 @@ impl Embeddings {
         .await
     }
 
+    /// Same as [`create`](Self::create), sent through `client` as with [`Credentials::with_client`].
+    pub async fn create_with_client(
+        model: &str,
+        input: Vec<&str>,
+        user: &str,
+        credentials: Credentials,
+        client: &Client,
+    ) -> ApiResponseOrError<Self> {
+        let credentials = credentials.with_client(client.clone());
+        Self::create(model, input, user, credentials).await
+    }
+
     pub fn distances(&self) -> Vec<f64> {
         let mut distances = Vec::new();
         let mut last_embedding: Option<&Embedding> = None;
@@ -75,25 +104,51 @@ impl Embeddings {
 
         distances
     }
+
+    /// Converts the embeddings to a matrix with one row per input,
+    /// of shape `[inputs, dimensions]`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the embeddings do not all have the same number of dimensions.
+    #[cfg(feature = "ndarray")]
+    pub fn to_array(&self) -> ndarray::Array2<f32> {
+        let dimensions = self.data.first().map_or(0, |embedding| embedding.vec.len());
+        let values = self
+            .data
+            .iter()
+            .flat_map(|embedding| embedding.vec.iter().map(|&value| value as f32))
+            .collect();
+        ndarray::Array2::from_shape_vec((self.data.len(), dimensions), values)
+            .expect("embeddings should have the same number of dimensions")
+    }
 }
 
-impl Embedding {
+impl EmbeddingsF32 {
+    /// Creates single precision embedding vectors representing the input text.
+    /// See [`Embeddings::create`] for the arguments.
     pub async fn create(
         model: &str,
-        input: &str,
+        input: Vec<&str>,
         user: &str,
         credentials: Credentials,
     ) -> ApiResponseOrError<Self> {
-        let mut embeddings = Embeddings::create(model, vec![input], user, credentials).await?;
-        Ok(embeddings.data.swap_remove(0))
+        openai_post(
+            "embeddings",
+            &CreateEmbeddingsRequestBody { model, input, user },
+            Some(credentials),
+        )
+        .await
     }
+}
 
-    pub fn magnitude(&self) -> f64 {
-        self.vec.iter().map(|x| x * x).sum::<f64>().sqrt()
+impl EmbeddingF32 {
+    pub fn magnitude(&self) -> f32 {
+        self.vec.iter().map(|x| x * x).sum::<f32>().sqrt()
     }
 
-    pub fn distance(&self, other: &Self) -> f64 {
-        let dot_product: f64 = self
+    pub fn distance(&self, other: &Self) -> f32 {
+        let dot_product: f32 = self
             .vec
             .iter()
             .zip(other.vec.iter())
@@ -105,6 +160,60 @@ impl Embedding {
     }
 }
 
+impl From<Embedding> for EmbeddingF32 {
+    fn from(embedding: Embedding) -> Self {
+        EmbeddingF32 {
+            vec: embedding
+                .vec
+                .into_iter()
+                .map(|value| value as f32)
+                .collect(),
+        }
+    }
+}
+
+impl Embedding {
+    pub async fn create(
+        model: &str,
+        input: &str,
+        user: &str,
+        credentials: Credentials,
+    ) -> ApiResponseOrError<Self> {
+        let mut embeddings = Embeddings::create(model, vec![input], user, credentials).await?;
+        Ok(embeddings.data.swap_remove(0))
+    }
+
+    pub fn magnitude(&self) -> f64 {
+        self.vec.iter().map(|x| x * x).sum::<f64>().sqrt()
+    }
+
+    pub fn distance(&self, other: &Self) -> f64 {
+        1.0 - cosine_similarity(&self.vec, &other.vec)
+    }
+}
+
+/// The cosine similarity of two vectors, such as embeddings stored elsewhere,
+/// from -1 for opposite directions to 1 for the same direction.
+pub fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot_product: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let magnitude = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+    dot_product / (magnitude(a) * magnitude(b))
+}
+
+/// Finds the `k` vectors in `corpus` most similar to `query`,
+/// returning their indices and cosine similarities, from most to least similar.
+pub fn top_k(query: &[f64], corpus: &[Vec<f64>], k: usize) -> Vec<(usize, f64)> {
+    let mut similarities: Vec<_> = corpus
+        .iter()
+        .map(|vector| cosine_similarity(query, vector))
+        .enumerate()
+        .collect();
+    similarities.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    similarities.truncate(k);
+    similarities
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,4 +293,89 @@ mod tests {
 
         assert_eq!(embeddings.distances()[0], 0.29289321881345254);
     }
+
+    #[test]
+    fn raw_slice_similarity() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert!((cosine_similarity(&[1.0, 2.0], &[2.0, 4.0]) - 1.0).abs() < 1e-12);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[-3.0, 0.0]), -1.0);
+
+        let corpus = vec![
+            vec![-0.72, 0.31, 0.05, 0.61],
+            vec![0.11, -0.49, 0.86, 0.02],
+            vec![0.35, -0.12, 0.64, -0.67],
+        ];
+        let query = [0.12, -0.53, 0.84, 0.07];
+        let top = top_k(&query, &corpus, 2);
+
+        assert_eq!(top.iter().map(|(i, _)| *i).collect::<Vec<_>>(), [1, 2]);
+        assert!(top[0].1 > top[1].1);
+        assert_eq!(top_k(&query, &corpus, 10).len(), 3);
+        assert!(top_k(&query, &[], 2).is_empty());
+    }
+
+    #[test]
+    fn f32_similarity_ranking() {
+        let response = serde_json::json!({
+            "object": "list",
+            "data": [
+                { "object": "embedding", "index": 0, "embedding": [0.12, -0.53, 0.84, 0.07] },
+                { "object": "embedding", "index": 1, "embedding": [0.11, -0.49, 0.86, 0.02] },
+                { "object": "embedding", "index": 2, "embedding": [-0.72, 0.31, 0.05, 0.61] },
+                { "object": "embedding", "index": 3, "embedding": [0.35, -0.12, 0.64, -0.67] },
+            ],
+            "model": "text-embedding-3-small",
+            "usage": { "prompt_tokens": 8, "total_tokens": 8 },
+        });
+        let embeddings: Embeddings = serde_json::from_value(response.clone()).unwrap();
+        let embeddings_f32: EmbeddingsF32 = serde_json::from_value(response).unwrap();
+
+        let mut ranking = (1..4).collect::<Vec<_>>();
+        ranking.sort_by(|&a, &b| {
+            let query = &embeddings.data[0];
+            query
+                .distance(&embeddings.data[a])
+                .total_cmp(&query.distance(&embeddings.data[b]))
+        });
+        let mut ranking_f32 = (1..4).collect::<Vec<_>>();
+        ranking_f32.sort_by(|&a, &b| {
+            let query = &embeddings_f32.data[0];
+            query
+                .distance(&embeddings_f32.data[a])
+                .total_cmp(&query.distance(&embeddings_f32.data[b]))
+        });
+
+        assert_eq!(ranking, [1, 3, 2]);
+        assert_eq!(ranking, ranking_f32);
+        let converted = EmbeddingF32::from(embeddings.data[1].clone());
+        assert_eq!(converted.vec, embeddings_f32.data[1].vec);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn to_array_shape() {
+        let embeddings = Embeddings {
+            data: vec![
+                Embedding {
+                    vec: vec![1.0, 0.0, 0.0, 0.5],
+                },
+                Embedding {
+                    vec: vec![0.0, 1.0, 0.0, 0.5],
+                },
+                Embedding {
+                    vec: vec![0.0, 0.0, 1.0, 0.5],
+                },
+            ],
+            model: "text-embedding-3-small".to_string(),
+            usage: EmbeddingsUsage {
+                prompt_tokens: 0,
+                total_tokens: 0,
+            },
+        };
+        let array = embeddings.to_array();
+
+        assert_eq!(array.shape(), [3, 4]);
+        assert_eq!(array[[1, 1]], 1.0);
+        assert_eq!(array[[2, 3]], 0.5);
+    }
 }
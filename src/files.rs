@@ -10,7 +10,7 @@
 //!
 //! Upload a new file. [Reference API](https://platform.openai.com/docs/api-reference/files/upload)
 //! ```
-//!use openai::files::File;
+//!use openai::files::{File, FilePurpose};
 //!use openai::ApiResponseOrError;
 //!use dotenvy::dotenv;
 //!use std::env;
@@ -22,7 +22,7 @@
 //!     let credentials = Credentials::from_env();
 //!     let uploaded_file = File::builder()
 //!         .file_name("test_data/file_upload_test1.jsonl") // local file path to upload.
-//!         .purpose("fine-tune")
+//!         .purpose(FilePurpose::FineTune)
 //!         .create()
 //!         .await?;
 //!     assert_eq!(uploaded_file.filename, "file_upload_test1.jsonl");
@@ -114,17 +114,19 @@
 //! For more examples see the files tests.
 //!
 
-use std::io::Write;
 use std::path::Path;
 
-use bytes::{BufMut, BytesMut};
 use derive_builder::Builder;
 use futures_util::StreamExt;
 use reqwest::multipart::{Form, Part};
-use reqwest::Method;
+use reqwest::{Body, Method};
 use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 
-use crate::{openai_delete, openai_get, openai_post_multipart, openai_request, Credentials};
+use crate::{
+    mime_type, openai_delete, openai_get, openai_post_multipart, openai_request_bytes,
+    openai_request_ok, Credentials,
+};
 
 use super::ApiResponseOrError;
 
@@ -160,13 +162,43 @@ pub struct Files {
     pub object: String,
 }
 
+/// The intended purpose of an uploaded file.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FilePurpose {
+    #[serde(rename = "assistants")]
+    Assistants,
+    #[serde(rename = "batch")]
+    Batch,
+    #[serde(rename = "fine-tune")]
+    FineTune,
+    #[serde(rename = "vision")]
+    Vision,
+    #[serde(rename = "user_data")]
+    UserData,
+    #[serde(rename = "evals")]
+    Evals,
+}
+
+impl FilePurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilePurpose::Assistants => "assistants",
+            FilePurpose::Batch => "batch",
+            FilePurpose::FineTune => "fine-tune",
+            FilePurpose::Vision => "vision",
+            FilePurpose::UserData => "user_data",
+            FilePurpose::Evals => "evals",
+        }
+    }
+}
+
 #[derive(Serialize, Builder, Debug, Clone)]
 #[builder(pattern = "owned")]
 #[builder(name = "FileUploadBuilder")]
 #[builder(setter(strip_option, into))]
 pub struct FileUploadRequest {
     file_name: String,
-    purpose: String,
+    purpose: FilePurpose,
     /// The credentials to use for this request.
     #[serde(skip_serializing)]
     #[builder(default)]
@@ -185,15 +217,61 @@ impl File {
             .to_string()
             .clone();
         let async_file = tokio::fs::File::open(upload_file_path).await?;
+        let mime = mime_type(Path::new(&simple_name));
         let file_part = Part::stream(async_file)
             .file_name(simple_name)
-            .mime_str("application/jsonl")?;
+            .mime_str(mime)?;
         let form = Form::new()
             .part("file", file_part)
-            .text("purpose", request.purpose);
+            .text("purpose", request.purpose.as_str());
         openai_post_multipart("files", form, request.credentials).await
     }
 
+    /// Upload in-memory content as a file to the openai platform,
+    /// without having to write it to the local filesystem first.
+    /// The content type is inferred from the extension of `file_name`.
+    pub async fn create_from_bytes<B>(
+        file_name: &str,
+        purpose: FilePurpose,
+        bytes: B,
+        credentials: Credentials,
+    ) -> ApiResponseOrError<Self>
+    where
+        B: Into<Body>,
+    {
+        let file_part = Part::stream(bytes)
+            .file_name(file_name.to_string())
+            .mime_str(mime_type(Path::new(file_name)))?;
+        let form = Form::new()
+            .part("file", file_part)
+            .text("purpose", purpose.as_str());
+        openai_post_multipart("files", form, Some(credentials)).await
+    }
+
+    /// Uploads several files concurrently, with at most `max_concurrency` uploads in flight.
+    /// The results are returned in the same order as `file_names`, each upload failing independently.
+    pub async fn create_many<P>(
+        file_names: impl IntoIterator<Item = P>,
+        purpose: FilePurpose,
+        max_concurrency: usize,
+        credentials: Credentials,
+    ) -> Vec<ApiResponseOrError<Self>>
+    where
+        P: Into<String>,
+    {
+        futures_util::stream::iter(file_names)
+            .map(|file_name| {
+                File::builder()
+                    .file_name(file_name)
+                    .purpose(purpose)
+                    .credentials(credentials.clone())
+                    .create()
+            })
+            .buffered(max_concurrency.max(1))
+            .collect()
+            .await
+    }
+
     /// New FileUploadBuilder
     pub fn builder() -> FileUploadBuilder {
         FileUploadBuilder::create_empty()
@@ -234,20 +312,14 @@ impl File {
         credentials_opt: Option<Credentials>,
     ) -> ApiResponseOrError<Vec<u8>> {
         let route = format!("files/{}/content", id);
-        let response = openai_request(
+        let bytes = openai_request_bytes(
             Method::GET,
             route.as_str(),
             |request| request,
             credentials_opt,
         )
         .await?;
-        let content_len = response.content_length().unwrap_or(1024) as usize;
-        let mut file_bytes = BytesMut::with_capacity(content_len);
-        let mut bytes_stream = response.bytes_stream();
-        while let Some(Ok(bytes)) = bytes_stream.next().await {
-            file_bytes.put(bytes);
-        }
-        Ok(file_bytes.to_vec())
+        Ok(bytes.to_vec())
     }
 
     /// Download a file to a new local file by id.
@@ -256,9 +328,22 @@ impl File {
         file_path: &str,
         credentials: Credentials,
     ) -> ApiResponseOrError<()> {
-        let mut output_file = std::fs::File::create(file_path)?;
+        let mut output_file = tokio::fs::File::create(file_path).await?;
+        Self::download_content_to_writer(id, &mut output_file, credentials).await
+    }
+
+    /// Download a file by id, writing its contents to `writer` as they arrive
+    /// rather than buffering the whole file in memory.
+    pub async fn download_content_to_writer<W>(
+        id: &str,
+        writer: &mut W,
+        credentials: Credentials,
+    ) -> ApiResponseOrError<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
         let route = format!("files/{}/content", id);
-        let response = openai_request(
+        let response = openai_request_ok(
             Method::GET,
             route.as_str(),
             |request| request,
@@ -266,9 +351,10 @@ impl File {
         )
         .await?;
         let mut bytes_stream = response.bytes_stream();
-        while let Some(Ok(bytes)) = bytes_stream.next().await {
-            output_file.write_all(bytes.as_ref())?;
+        while let Some(bytes) = bytes_stream.next().await {
+            writer.write_all(bytes?.as_ref()).await?;
         }
+        writer.flush().await?;
         Ok(())
     }
 }
@@ -314,13 +400,33 @@ mod tests {
     fn test_upload_builder() -> FileUploadBuilder {
         File::builder()
             .file_name("test_data/file_upload_test1.jsonl")
-            .purpose("fine-tune")
+            .purpose(FilePurpose::FineTune)
     }
 
     fn test_upload_request() -> FileUploadRequest {
         test_upload_builder().build().unwrap()
     }
 
+    #[test]
+    fn upload_purpose_serialization() {
+        let request = File::builder()
+            .file_name("test_data/file_upload_test1.jsonl")
+            .purpose(FilePurpose::UserData)
+            .build()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "file_name": "test_data/file_upload_test1.jsonl",
+                "purpose": "user_data",
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(FilePurpose::FineTune).unwrap(),
+            FilePurpose::FineTune.as_str()
+        );
+    }
+
     #[tokio::test]
     async fn upload_file() {
         dotenv().ok();
@@ -337,6 +443,65 @@ mod tests {
         assert_eq!(file_upload.id.as_bytes()[..5], *"file-".as_bytes())
     }
 
+    #[tokio::test]
+    async fn upload_file_from_bytes() {
+        dotenv().ok();
+        let credentials = Credentials::from_env();
+        let content = std::fs::read("test_data/file_upload_test1.jsonl").unwrap();
+        let file_upload = File::create_from_bytes(
+            "in_memory.jsonl",
+            FilePurpose::FineTune,
+            content.clone(),
+            credentials,
+        )
+        .await
+        .unwrap();
+        assert_eq!(file_upload.filename, "in_memory.jsonl");
+        assert_eq!(file_upload.bytes, content.len());
+    }
+
+    #[tokio::test]
+    async fn upload_bytes_mime_type() {
+        let (credentials, server) = crate::tests::mock_server(
+            200,
+            r#"{"id": "file-abc123", "object": "file", "bytes": 69, "created_at": 1613779121, "filename": "pixel.png", "purpose": "vision"}"#,
+        )
+        .await;
+        let content = std::fs::read("test_data/pixel.png").unwrap();
+        File::create_from_bytes("pixel.png", FilePurpose::Vision, content, credentials)
+            .await
+            .unwrap();
+        let request = server.await.unwrap().to_lowercase();
+        assert!(request.contains("content-type: image/png"));
+        assert!(!request.contains("application/jsonl"));
+    }
+
+    #[tokio::test]
+    async fn upload_many_files() {
+        let (credentials, server) = crate::tests::mock_server_connections(
+            2,
+            200,
+            r#"{"id": "file-abc123", "object": "file", "bytes": 140, "created_at": 1613779121, "filename": "upload.jsonl", "purpose": "fine-tune"}"#,
+        )
+        .await;
+        let results = File::create_many(
+            [
+                "test_data/file_upload_test1.jsonl",
+                "test_data/file_upload_test2.jsonl",
+            ],
+            FilePurpose::FineTune,
+            2,
+            credentials,
+        )
+        .await;
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.is_ok()));
+        let requests = server.await.unwrap().join("\n");
+        assert!(requests.contains(r#"filename="file_upload_test1.jsonl""#));
+        assert!(requests.contains(r#"filename="file_upload_test2.jsonl""#));
+    }
+
     #[tokio::test]
     async fn missing_file() {
         dotenv().ok();
@@ -344,7 +509,7 @@ mod tests {
         let test_builder = File::builder()
             .file_name("test_data/missing_file.jsonl")
             .credentials(credentials)
-            .purpose("fine-tune");
+            .purpose(FilePurpose::FineTune);
         let response = test_builder.create().await;
         assert!(response.is_err());
         let openapi_err = response.err().unwrap();
@@ -433,6 +598,34 @@ mod tests {
         assert_eq!(body_bytes, local_bytes)
     }
 
+    #[tokio::test]
+    async fn download_content_to_writer() {
+        let (credentials, server) = crate::tests::mock_server(200, "file contents").await;
+        let mut contents = Vec::new();
+        File::download_content_to_writer("file-abc123", &mut contents, credentials)
+            .await
+            .unwrap();
+        assert_eq!(contents, b"file contents");
+        assert!(server
+            .await
+            .unwrap()
+            .starts_with("GET /v1/files/file-abc123/content "));
+    }
+
+    #[tokio::test]
+    async fn fetch_missing_content() {
+        let (credentials, _server) = crate::tests::mock_server(
+            404,
+            r#"{"error": {"message": "No such File object: file-abc123", "type": "invalid_request_error", "param": "id", "code": null}}"#,
+        )
+        .await;
+        let error = File::fetch_content_bytes("file-abc123", credentials)
+            .await
+            .unwrap_err();
+        assert_eq!(error.error_type, "invalid_request_error");
+        assert_eq!(error.message, "No such File object: file-abc123");
+    }
+
     #[test]
     fn file_name_path_test() {
         let request = test_upload_request();
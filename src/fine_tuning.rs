@@ -0,0 +1,288 @@
+//! Manage fine-tuning jobs to tailor a model to your specific training data.
+//!
+//! Related guide: [Fine-tune models](https://platform.openai.com/docs/guides/fine-tuning)
+
+use super::{openai_get, openai_post, openai_request_json, ApiResponseOrError, Credentials};
+use crate::RequestPagination;
+use derive_builder::Builder;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct FineTuningJob {
+    pub id: String,
+    pub object: String,
+    /// Unix timestamp, in seconds, of when the fine-tuning job was created.
+    pub created_at: u64,
+    /// Unix timestamp, in seconds, of when the fine-tuning job was finished.
+    /// `None` while the job is still running.
+    pub finished_at: Option<u64>,
+    /// The base model that is being fine-tuned.
+    pub model: String,
+    /// The name of the fine-tuned model that is being created.
+    /// `None` while the job is still running.
+    pub fine_tuned_model: Option<String>,
+    /// The organization that owns the fine-tuning job.
+    /// Some OpenAI-compatible providers omit it.
+    pub organization_id: Option<String>,
+    pub status: FineTuningJobStatus,
+    pub hyperparameters: Hyperparameters,
+    /// The file ID used for training.
+    pub training_file: String,
+    /// The file ID used for validation.
+    pub validation_file: Option<String>,
+    /// The compiled results file ID(s) for the fine-tuning job.
+    #[serde(default)]
+    pub result_files: Vec<String>,
+    /// The total number of billable tokens processed by this fine-tuning job.
+    pub trained_tokens: Option<u64>,
+    /// For fine-tuning jobs that have failed, this will contain more information on the cause of the failure.
+    pub error: Option<FineTuningJobError>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FineTuningJobStatus {
+    ValidatingFiles,
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+    /// A status not known to this crate. It is not treated as terminal.
+    #[serde(other)]
+    Unknown,
+}
+
+impl FineTuningJobStatus {
+    /// Whether the job has stopped and its status will not change anymore.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            FineTuningJobStatus::Succeeded
+                | FineTuningJobStatus::Failed
+                | FineTuningJobStatus::Cancelled
+        )
+    }
+}
+
+/// The hyperparameters used for the fine-tuning job.
+/// Each one is either the string `"auto"` or a number.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+pub struct Hyperparameters {
+    /// The number of epochs to train the model for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_epochs: Option<Value>,
+    /// Number of examples in each batch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<Value>,
+    /// Scaling factor for the learning rate.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub learning_rate_multiplier: Option<Value>,
+}
+
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct FineTuningJobError {
+    pub code: String,
+    pub message: String,
+    pub param: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct FineTuningJobs {
+    pub data: Vec<FineTuningJob>,
+    pub has_more: bool,
+}
+
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct FineTuningJobEvent {
+    pub id: String,
+    pub object: String,
+    pub created_at: u64,
+    pub level: String,
+    pub message: String,
+}
+
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct FineTuningJobEvents {
+    pub data: Vec<FineTuningJobEvent>,
+    pub has_more: bool,
+}
+
+#[derive(Serialize, Builder, Debug, Clone)]
+#[builder(derive(Clone, Debug, PartialEq))]
+#[builder(pattern = "owned")]
+#[builder(name = "CreateFineTuningJobBuilder")]
+#[builder(setter(strip_option, into))]
+pub struct CreateFineTuningJobRequest {
+    /// The name of the model to fine-tune.
+    pub model: String,
+    /// The ID of an uploaded file that contains training data, with the purpose `fine-tune`.
+    pub training_file: String,
+    /// The ID of an uploaded file that contains validation data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub validation_file: Option<String>,
+    /// The hyperparameters used for the fine-tuning job.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub hyperparameters: Option<Hyperparameters>,
+    /// A string of up to 64 characters that will be added to your fine-tuned model name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub suffix: Option<String>,
+    /// The seed controls the reproducibility of the job.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub seed: Option<u64>,
+    /// The credentials to use for this request.
+    #[serde(skip_serializing)]
+    #[builder(default)]
+    pub credentials: Option<Credentials>,
+}
+
+impl FineTuningJob {
+    /// Creates a fine-tuning job which begins the process of creating a new model from a given dataset.
+    async fn create(request: CreateFineTuningJobRequest) -> ApiResponseOrError<Self> {
+        let credentials_opt = request.credentials.clone();
+        openai_post("fine_tuning/jobs", &request, credentials_opt).await
+    }
+
+    pub fn builder(
+        model: impl Into<String>,
+        training_file: impl Into<String>,
+    ) -> CreateFineTuningJobBuilder {
+        CreateFineTuningJobBuilder::create_empty()
+            .model(model)
+            .training_file(training_file)
+    }
+
+    /// Get info about a fine-tuning job.
+    pub async fn fetch(id: &str, credentials: Credentials) -> ApiResponseOrError<Self> {
+        openai_get(&format!("fine_tuning/jobs/{id}"), Some(credentials)).await
+    }
+
+    /// List your organization's fine-tuning jobs.
+    pub async fn list(
+        pagination: RequestPagination,
+        credentials: Credentials,
+    ) -> ApiResponseOrError<FineTuningJobs> {
+        openai_request_json(
+            Method::GET,
+            "fine_tuning/jobs",
+            |request| request.query(&pagination),
+            Some(credentials),
+        )
+        .await
+    }
+
+    /// Immediately cancel a fine-tuning job.
+    pub async fn cancel(id: &str, credentials: Credentials) -> ApiResponseOrError<Self> {
+        openai_request_json(
+            Method::POST,
+            &format!("fine_tuning/jobs/{id}/cancel"),
+            |request| request,
+            Some(credentials),
+        )
+        .await
+    }
+
+    /// Get status updates for a fine-tuning job.
+    pub async fn list_events(
+        id: &str,
+        pagination: RequestPagination,
+        credentials: Credentials,
+    ) -> ApiResponseOrError<FineTuningJobEvents> {
+        openai_request_json(
+            Method::GET,
+            &format!("fine_tuning/jobs/{id}/events"),
+            |request| request.query(&pagination),
+            Some(credentials),
+        )
+        .await
+    }
+}
+
+impl CreateFineTuningJobBuilder {
+    pub async fn create(self) -> ApiResponseOrError<FineTuningJob> {
+        FineTuningJob::create(self.build().unwrap()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dotenvy::dotenv;
+
+    #[tokio::test]
+    async fn list_fine_tuning_jobs() {
+        dotenv().ok();
+        let credentials = Credentials::from_env();
+
+        let jobs = FineTuningJob::list(
+            RequestPagination {
+                limit: Some(1),
+                ..Default::default()
+            },
+            credentials,
+        )
+        .await
+        .unwrap();
+
+        assert!(jobs.data.len() <= 1);
+    }
+
+    #[test]
+    fn builder_clone_and_eq() {
+        let builder_a = FineTuningJob::builder("gpt-4o-mini-2024-07-18", "file-abc123")
+            .suffix("custom-model")
+            .seed(42u64);
+        let builder_b = builder_a.clone();
+        let builder_c = builder_b.clone().validation_file("file-def456");
+        assert_eq!(builder_a, builder_b);
+        assert_ne!(builder_a, builder_c);
+
+        let request = builder_c.build().unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "model": "gpt-4o-mini-2024-07-18",
+                "training_file": "file-abc123",
+                "validation_file": "file-def456",
+                "suffix": "custom-model",
+                "seed": 42,
+            })
+        );
+    }
+
+    #[test]
+    fn job_from_compatible_provider() {
+        let job: FineTuningJob = serde_json::from_value(serde_json::json!({
+            "id": "ftjob-abc123",
+            "object": "fine_tuning.job",
+            "created_at": 1721764800,
+            "model": "gpt-4o-mini-2024-07-18",
+            "status": "paused",
+            "hyperparameters": { "n_epochs": "auto" },
+            "training_file": "file-abc123",
+        }))
+        .unwrap();
+
+        assert_eq!(job.organization_id, None);
+        assert_eq!(job.status, FineTuningJobStatus::Unknown);
+    }
+
+    #[test]
+    fn terminal_status() {
+        assert!(!FineTuningJobStatus::Running.is_terminal());
+        assert!(!FineTuningJobStatus::ValidatingFiles.is_terminal());
+        assert!(FineTuningJobStatus::Succeeded.is_terminal());
+        assert!(FineTuningJobStatus::Cancelled.is_terminal());
+
+        let status: FineTuningJobStatus =
+            serde_json::from_value(serde_json::json!("paused")).unwrap();
+        assert_eq!(status, FineTuningJobStatus::Unknown);
+        assert!(!status.is_terminal());
+    }
+}
@@ -0,0 +1,287 @@
+//! Given a prompt and/or an input image, the model will generate a new image.
+//!
+//! Related guide: [Image generation](https://platform.openai.com/docs/guides/images)
+
+use super::{file_part, openai_post, openai_post_multipart, ApiResponseOrError, Credentials};
+use derive_builder::Builder;
+use reqwest::multipart::Form;
+use serde::{Deserialize, Serialize};
+
+/// The images returned by a generation, edit or variation request.
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Images {
+    pub created: u64,
+    pub data: Vec<Image>,
+}
+
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Image {
+    /// The URL of the generated image, if `response_format` is `url` (default).
+    pub url: Option<String>,
+    /// The base64-encoded JSON of the generated image, if `response_format` is `b64_json`.
+    pub b64_json: Option<String>,
+    /// The prompt that was used to generate the image, if there was any revision to the prompt.
+    pub revised_prompt: Option<String>,
+}
+
+#[derive(Serialize, Builder, Debug, Clone)]
+#[builder(pattern = "owned")]
+#[builder(name = "ImageGenerationBuilder")]
+#[builder(setter(strip_option, into))]
+pub struct ImageGenerationRequest {
+    /// A text description of the desired image(s).
+    /// The maximum length is 1000 characters for `dall-e-2` and 4000 characters for `dall-e-3`.
+    pub prompt: String,
+    /// The model to use for image generation.
+    /// One of `dall-e-2`, `dall-e-3` or `gpt-image-1`. Defaults to `dall-e-2`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub model: Option<String>,
+    /// The number of images to generate. Must be between 1 and 10.
+    /// For `dall-e-3`, only `n=1` is supported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub n: Option<u8>,
+    /// The size of the generated images.
+    /// Must be one of `256x256`, `512x512`, or `1024x1024` for `dall-e-2`,
+    /// and one of `1024x1024`, `1792x1024`, or `1024x1792` for `dall-e-3`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub size: Option<String>,
+    /// The quality of the image that will be generated.
+    /// `hd` creates images with finer details and greater consistency across the image.
+    /// This param is only supported for `dall-e-3`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub quality: Option<String>,
+    /// The style of the generated images. Must be one of `vivid` or `natural`.
+    /// This param is only supported for `dall-e-3`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub style: Option<String>,
+    /// The format in which the generated images are returned. Must be one of `url` or `b64_json`.
+    /// URLs are only valid for 60 minutes after the image has been generated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub response_format: Option<String>,
+    /// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
+    /// [Learn more](https://platform.openai.com/docs/guides/safety-best-practices/end-user-ids).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[builder(default)]
+    pub user: Option<String>,
+    /// The credentials to use for this request.
+    #[serde(skip_serializing)]
+    #[builder(default)]
+    pub credentials: Option<Credentials>,
+}
+
+#[derive(Builder, Debug, Clone)]
+#[builder(pattern = "owned")]
+#[builder(name = "ImageEditBuilder")]
+#[builder(setter(strip_option, into))]
+pub struct ImageEditRequest {
+    /// The local path of the image to edit. Must be a valid PNG file, less than 4MB, and square,
+    /// or for `gpt-image-1` a PNG, JPEG or WebP file. Its content type follows the file extension.
+    /// If mask is not provided, image must have transparency, which will be used as the mask.
+    pub image: String,
+    /// A text description of the desired image(s). The maximum length is 1000 characters.
+    pub prompt: String,
+    /// The local path of an additional image whose fully transparent areas indicate where `image` should be edited.
+    /// Must be a valid PNG file, less than 4MB, and have the same dimensions as `image`.
+    #[builder(default)]
+    pub mask: Option<String>,
+    /// The model to use for image generation. Only `dall-e-2` and `gpt-image-1` are supported.
+    #[builder(default)]
+    pub model: Option<String>,
+    /// The number of images to generate. Must be between 1 and 10.
+    #[builder(default)]
+    pub n: Option<u8>,
+    /// The size of the generated images. Must be one of `256x256`, `512x512`, or `1024x1024`.
+    #[builder(default)]
+    pub size: Option<String>,
+    /// The format in which the generated images are returned. Must be one of `url` or `b64_json`.
+    #[builder(default)]
+    pub response_format: Option<String>,
+    /// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
+    #[builder(default)]
+    pub user: Option<String>,
+    /// The credentials to use for this request.
+    #[builder(default)]
+    pub credentials: Option<Credentials>,
+}
+
+#[derive(Builder, Debug, Clone)]
+#[builder(pattern = "owned")]
+#[builder(name = "ImageVariationBuilder")]
+#[builder(setter(strip_option, into))]
+pub struct ImageVariationRequest {
+    /// The local path of the image to use as the basis for the variation(s).
+    /// Must be a valid PNG file, less than 4MB, and square.
+    pub image: String,
+    /// The model to use for image generation. Only `dall-e-2` is supported at this time.
+    #[builder(default)]
+    pub model: Option<String>,
+    /// The number of images to generate. Must be between 1 and 10.
+    #[builder(default)]
+    pub n: Option<u8>,
+    /// The size of the generated images. Must be one of `256x256`, `512x512`, or `1024x1024`.
+    #[builder(default)]
+    pub size: Option<String>,
+    /// The format in which the generated images are returned. Must be one of `url` or `b64_json`.
+    #[builder(default)]
+    pub response_format: Option<String>,
+    /// A unique identifier representing your end-user, which can help OpenAI to monitor and detect abuse.
+    #[builder(default)]
+    pub user: Option<String>,
+    /// The credentials to use for this request.
+    #[builder(default)]
+    pub credentials: Option<Credentials>,
+}
+
+impl Images {
+    /// Creates an image given a prompt.
+    async fn create(request: ImageGenerationRequest) -> ApiResponseOrError<Self> {
+        let credentials_opt = request.credentials.clone();
+        openai_post("images/generations", &request, credentials_opt).await
+    }
+
+    /// Creates an edited or extended image given an original image and a prompt.
+    async fn create_edit(request: ImageEditRequest) -> ApiResponseOrError<Self> {
+        let mut form = Form::new()
+            .part("image", file_part(&request.image).await?)
+            .text("prompt", request.prompt);
+        if let Some(mask) = request.mask {
+            form = form.part("mask", file_part(&mask).await?);
+        }
+        form = with_common_fields(
+            form,
+            request.model,
+            request.n,
+            request.size,
+            request.response_format,
+            request.user,
+        );
+        openai_post_multipart("images/edits", form, request.credentials).await
+    }
+
+    /// Creates a variation of a given image.
+    async fn create_variation(request: ImageVariationRequest) -> ApiResponseOrError<Self> {
+        let mut form = Form::new().part("image", file_part(&request.image).await?);
+        form = with_common_fields(
+            form,
+            request.model,
+            request.n,
+            request.size,
+            request.response_format,
+            request.user,
+        );
+        openai_post_multipart("images/variations", form, request.credentials).await
+    }
+
+    pub fn builder(prompt: impl Into<String>) -> ImageGenerationBuilder {
+        ImageGenerationBuilder::create_empty().prompt(prompt)
+    }
+
+    pub fn edit_builder(image: impl Into<String>, prompt: impl Into<String>) -> ImageEditBuilder {
+        ImageEditBuilder::create_empty().image(image).prompt(prompt)
+    }
+
+    pub fn variation_builder(image: impl Into<String>) -> ImageVariationBuilder {
+        ImageVariationBuilder::create_empty().image(image)
+    }
+}
+
+fn with_common_fields(
+    mut form: Form,
+    model: Option<String>,
+    n: Option<u8>,
+    size: Option<String>,
+    response_format: Option<String>,
+    user: Option<String>,
+) -> Form {
+    if let Some(model) = model {
+        form = form.text("model", model);
+    }
+    if let Some(n) = n {
+        form = form.text("n", n.to_string());
+    }
+    if let Some(size) = size {
+        form = form.text("size", size);
+    }
+    if let Some(response_format) = response_format {
+        form = form.text("response_format", response_format);
+    }
+    if let Some(user) = user {
+        form = form.text("user", user);
+    }
+    form
+}
+
+impl ImageGenerationBuilder {
+    pub async fn create(self) -> ApiResponseOrError<Images> {
+        Images::create(self.build().unwrap()).await
+    }
+}
+
+impl ImageEditBuilder {
+    pub async fn create(self) -> ApiResponseOrError<Images> {
+        Images::create_edit(self.build().unwrap()).await
+    }
+}
+
+impl ImageVariationBuilder {
+    pub async fn create(self) -> ApiResponseOrError<Images> {
+        Images::create_variation(self.build().unwrap()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dotenvy::dotenv;
+
+    #[tokio::test]
+    async fn edit_mime_types() {
+        // Only the extension matters for the content type of the upload.
+        let image = std::env::temp_dir().join("openai-rs-edit-input.jpg");
+        std::fs::copy("test_data/pixel.png", &image).unwrap();
+        let (credentials, server) =
+            crate::tests::mock_server(200, r#"{"created": 1589478378, "data": []}"#).await;
+
+        Images::edit_builder(image.to_str().unwrap(), "Add a crab")
+            .mask("test_data/pixel.png")
+            .model("gpt-image-1")
+            .credentials(credentials)
+            .create()
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap().to_lowercase();
+        assert!(request.contains("content-type: image/jpeg"));
+        assert!(request.contains("content-type: image/png"));
+    }
+
+    #[tokio::test]
+    async fn image_generation() {
+        dotenv().ok();
+        let credentials = Credentials::from_env();
+
+        let images = Images::builder("A cute baby sea otter")
+            .model("dall-e-2")
+            .n(1)
+            .size("256x256")
+            .credentials(credentials)
+            .create()
+            .await
+            .unwrap();
+
+        assert!(!images
+            .data
+            .first()
+            .unwrap()
+            .url
+            .as_ref()
+            .unwrap()
+            .is_empty());
+    }
+}
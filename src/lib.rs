@@ -1,53 +1,274 @@
-use reqwest::multipart::Form;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use reqwest::multipart::{Form, Part};
 use reqwest::{header::AUTHORIZATION, Client, Method, RequestBuilder, Response};
-use reqwest_eventsource::{CannotCloneRequestError, EventSource, RequestBuilderExt};
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use reqwest_eventsource::{CannotCloneRequestError, Event, EventSource, RequestBuilderExt};
+use serde::{de::DeserializeOwned, de::IgnoredAny, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
 use std::env;
 use std::env::VarError;
-use std::sync::{LazyLock, RwLock};
+use std::path::Path;
+use std::sync::{Arc, LazyLock, RwLock};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
 
+/// Implements the setters of the shared [`SamplingParams`] on a request builder
+/// with a `sampling` field, so they can be set one at a time like any other field.
+macro_rules! sampling_setters {
+    ($builder:ty) => {
+        impl $builder {
+            /// Sets all sampling parameters at once, replacing any set before.
+            pub fn sampling(mut self, sampling: $crate::SamplingParams) -> Self {
+                self.sampling = Some(sampling);
+                self
+            }
+
+            pub fn temperature(mut self, temperature: f32) -> Self {
+                self.sampling_mut().temperature = Some(temperature);
+                self
+            }
+
+            pub fn top_p(mut self, top_p: f32) -> Self {
+                self.sampling_mut().top_p = Some(top_p);
+                self
+            }
+
+            pub fn presence_penalty(mut self, presence_penalty: f32) -> Self {
+                self.sampling_mut().presence_penalty = Some(presence_penalty);
+                self
+            }
+
+            pub fn frequency_penalty(mut self, frequency_penalty: f32) -> Self {
+                self.sampling_mut().frequency_penalty = Some(frequency_penalty);
+                self
+            }
+
+            pub fn stop(mut self, stop: impl Into<Vec<String>>) -> Self {
+                self.sampling_mut().stop = stop.into();
+                self
+            }
+
+            pub fn logit_bias(
+                mut self,
+                logit_bias: impl Into<std::collections::HashMap<String, i16>>,
+            ) -> Self {
+                self.sampling_mut().logit_bias = logit_bias.into();
+                self
+            }
+
+            fn sampling_mut(&mut self) -> &mut $crate::SamplingParams {
+                self.sampling.get_or_insert_with(Default::default)
+            }
+        }
+    };
+}
+
+pub mod audio;
+pub mod batch;
 pub mod chat;
 pub mod completions;
 pub mod edits;
 pub mod embeddings;
 pub mod files;
+pub mod fine_tuning;
+pub mod images;
 pub mod models;
 pub mod moderations;
+pub mod tokens;
 
 pub static DEFAULT_BASE_URL: LazyLock<String> =
     LazyLock::new(|| String::from("https://api.openai.com/v1/"));
 static DEFAULT_CREDENTIALS: LazyLock<RwLock<Credentials>> =
-    LazyLock::new(|| RwLock::new(Credentials::from_env()));
+    LazyLock::new(|| RwLock::new(Credentials::default_from_vars(|name| env::var(name))));
 
 /// Holds the API key and base URL for an OpenAI-compatible API.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Credentials {
     api_key: String,
     base_url: String,
+    extra_headers: HashMap<String, String>,
+    flavor: ApiFlavor,
+    client: Option<Client>,
+    default_model: Option<String>,
+}
+
+/// The layout of an OpenAI-compatible API, which decides how requests are authorized and routed.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub enum ApiFlavor {
+    /// The public OpenAI layout, authorized with an `Authorization: Bearer` header.
+    #[default]
+    OpenAi,
+    /// Azure OpenAI, authorized with an `api-key` header,
+    /// with the `api-version` query parameter added to every request.
+    Azure { api_version: String },
 }
 
+/// Credentials are compared by their contents, ignoring any custom HTTP client.
+impl PartialEq for Credentials {
+    fn eq(&self, other: &Self) -> bool {
+        self.api_key == other.api_key
+            && self.base_url == other.base_url
+            && self.extra_headers == other.extra_headers
+            && self.flavor == other.flavor
+            && self.default_model == other.default_model
+    }
+}
+
+impl Eq for Credentials {}
+
 impl Credentials {
+    /// Creates credentials for the given API key and base URL.
+    /// An empty or whitespace-only base URL is replaced by the default.
+    ///
+    /// The base URL is not validated; use [`Credentials::try_new`] for that.
     pub fn new(api_key: impl Into<String>, base_url: impl Into<String>) -> Self {
         let base_url = parse_base_url(base_url.into());
         Self {
             api_key: api_key.into(),
             base_url,
+            extra_headers: HashMap::new(),
+            flavor: ApiFlavor::OpenAi,
+            client: None,
+            default_model: None,
+        }
+    }
+
+    /// Same as [`Credentials::new`], but returns an error if the base URL
+    /// is not a well-formed HTTP or HTTPS URL.
+    // Returns the same error type as every request, rather than a boxed one.
+    #[allow(clippy::result_large_err)]
+    pub fn try_new(
+        api_key: impl Into<String>,
+        base_url: impl Into<String>,
+    ) -> Result<Self, OpenAiError> {
+        let credentials = Self::new(api_key, base_url);
+        let invalid = |reason: String| {
+            OpenAiError::new(
+                format!("invalid base URL {:?}: {reason}", credentials.base_url),
+                "invalid_base_url".to_string(),
+            )
+        };
+        let url = reqwest::Url::parse(&credentials.base_url)
+            .map_err(|e| invalid(e.to_string()).with_source(e))?;
+        if !matches!(url.scheme(), "http" | "https") || !url.has_host() {
+            return Err(invalid("expected an HTTP or HTTPS URL".to_string()));
+        }
+        Ok(credentials)
+    }
+
+    /// Creates credentials for an Azure OpenAI deployment, such as
+    /// `Credentials::azure(key, "https://my-resource.openai.azure.com", "gpt-4o", "2024-10-21")`.
+    ///
+    /// Requests are routed to `{endpoint}/openai/deployments/{deployment}/`,
+    /// so the model set on a request is ignored in favour of the deployment's.
+    pub fn azure(
+        api_key: impl Into<String>,
+        endpoint: impl Into<String>,
+        deployment: &str,
+        api_version: impl Into<String>,
+    ) -> Self {
+        let endpoint = parse_base_url(endpoint.into());
+        Self {
+            flavor: ApiFlavor::Azure {
+                api_version: api_version.into(),
+            },
+            ..Self::new(
+                api_key,
+                format!("{endpoint}openai/deployments/{deployment}/"),
+            )
         }
     }
 
     /// Fetches the credentials from the ENV variables
-    /// OPENAI_KEY and OPENAI_BASE_URL.
+    /// OPENAI_KEY, OPENAI_BASE_URL and OPENAI_DEFAULT_MODEL.
     /// # Panics
     /// This function will panic if the key variable is missing from the env.
     /// If only the base URL variable is missing, it will use the default.
+    /// The default model is optional.
     pub fn from_env() -> Credentials {
-        let api_key = env::var("OPENAI_KEY").unwrap();
-        let base_url_unparsed = env::var("OPENAI_BASE_URL").unwrap_or_else(|e| match e {
-            VarError::NotPresent => DEFAULT_BASE_URL.clone(),
-            VarError::NotUnicode(v) => panic!("OPENAI_BASE_URL is not unicode: {v:#?}"),
-        });
-        let base_url = parse_base_url(base_url_unparsed);
-        Credentials { api_key, base_url }
+        Self::try_from_env().unwrap()
+    }
+
+    /// Same as [`Credentials::from_env`], but returns an error instead of panicking
+    /// if the key variable is missing, or if a variable is not valid unicode.
+    pub fn try_from_env() -> Result<Credentials, VarError> {
+        Self::from_vars(|name| env::var(name))
+    }
+
+    /// Same as [`Credentials::try_from_env`], but with the variable names prefixed,
+    /// such as `GROQ_OPENAI_KEY` and `GROQ_OPENAI_BASE_URL` for the prefix `GROQ_`.
+    /// This allows the credentials of several providers to live in the same environment.
+    pub fn from_env_prefixed(prefix: &str) -> Result<Credentials, VarError> {
+        Self::from_vars_prefixed(prefix, |name| env::var(name))
+    }
+
+    fn from_vars_prefixed(
+        prefix: &str,
+        var: impl Fn(&str) -> Result<String, VarError>,
+    ) -> Result<Credentials, VarError> {
+        Self::from_vars(|name| var(&format!("{prefix}{name}")))
+    }
+
+    /// Reads the credentials from a file of `NAME=value` lines, using the same variable names
+    /// as [`Credentials::from_env`]. Empty lines and lines starting with `#` are ignored,
+    /// and values may be quoted.
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Credentials> {
+        let contents = std::fs::read_to_string(path)?;
+        let vars: HashMap<&str, &str> = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .map(|(name, value)| {
+                let name = name.trim();
+                let name = name.strip_prefix("export ").unwrap_or(name).trim();
+                let value = value.trim();
+                let value = ['"', '\'']
+                    .iter()
+                    .find_map(|quote| value.strip_prefix(*quote)?.strip_suffix(*quote))
+                    .unwrap_or(value);
+                (name, value)
+            })
+            .collect();
+        Self::from_vars(|name| {
+            vars.get(name)
+                .map(|value| value.to_string())
+                .ok_or(VarError::NotPresent)
+        })
+        .map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "OPENAI_KEY is missing from the credentials file",
+            )
+        })
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Result<String, VarError>) -> Result<Credentials, VarError> {
+        let api_key = var("OPENAI_KEY")?;
+        let base_url = match var("OPENAI_BASE_URL") {
+            Ok(base_url) => parse_base_url(base_url),
+            Err(VarError::NotPresent) => DEFAULT_BASE_URL.clone(),
+            Err(error) => return Err(error),
+        };
+        Ok(Credentials {
+            api_key,
+            base_url,
+            extra_headers: HashMap::new(),
+            flavor: ApiFlavor::OpenAi,
+            client: None,
+            default_model: var("OPENAI_DEFAULT_MODEL").ok(),
+        })
+    }
+
+    /// The credentials used by requests that are not given any.
+    /// Without a usable key variable, the key is left empty, so that the API
+    /// rejects the request instead of the process panicking on first use.
+    fn default_from_vars(var: impl Fn(&str) -> Result<String, VarError>) -> Credentials {
+        Self::from_vars(&var).unwrap_or_else(|_| {
+            let mut credentials = Credentials::new("", var("OPENAI_BASE_URL").unwrap_or_default());
+            credentials.default_model = var("OPENAI_DEFAULT_MODEL").ok();
+            credentials
+        })
     }
 
     pub fn api_key(&self) -> &str {
@@ -57,15 +278,96 @@ impl Credentials {
     pub fn base_url(&self) -> &str {
         &self.base_url
     }
+
+    /// Adds a header to be sent along with every request made with these credentials,
+    /// such as a correlation ID for distributed tracing.
+    ///
+    /// Since credentials are passed per request, cloning them and adding a header
+    /// scopes that header to a single request.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.extra_headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn extra_headers(&self) -> &HashMap<String, String> {
+        &self.extra_headers
+    }
+
+    pub fn flavor(&self) -> &ApiFlavor {
+        &self.flavor
+    }
+
+    /// Sends requests made with these credentials through the given HTTP client,
+    /// such as one configured with a proxy, root certificates or connection pool limits.
+    ///
+    /// The authorization and extra headers are still added to each request,
+    /// so the client does not need them as default headers.
+    ///
+    /// Clones of a client share its connection pool, so passing one client to many requests,
+    /// as the `create_with_client` methods do, reuses connections instead of opening new ones.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Sets the model used by requests made with these credentials that do not set one,
+    /// such as those started with [`ChatCompletion::builder_with_default_model`](chat::ChatCompletion::builder_with_default_model).
+    pub fn with_default_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = Some(model.into());
+        self
+    }
+
+    pub fn default_model(&self) -> Option<&str> {
+        self.default_model.as_deref()
+    }
+
+    /// Returns a copy of these credentials with a different API key,
+    /// keeping the base URL, headers and HTTP client.
+    ///
+    /// Clones of a client share its connection pool, so a server handling many API keys
+    /// can derive per-key credentials from one set without opening new connections for each.
+    pub fn with_api_key(&self, api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            ..self.clone()
+        }
+    }
+
+    fn client(&self) -> Client {
+        self.client.clone().unwrap_or_default()
+    }
 }
 
-#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct OpenAiError {
     pub message: String,
     #[serde(rename = "type")]
     pub error_type: String,
     pub param: Option<String>,
     pub code: Option<String>,
+    /// The HTTP status code of the response, for errors returned by the API.
+    #[serde(skip)]
+    pub status: Option<u16>,
+    /// Any other fields of the error, such as the `innererror` and `request_id`
+    /// some OpenAI-compatible providers return.
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+    /// The raw response body, for responses which could not be deserialized,
+    /// such as when the API or a compatible provider returns an unexpected shape.
+    /// These errors have the `deserialize` error type, and convert into [`Error::Deserialize`].
+    #[serde(skip)]
+    pub body: Option<String>,
+    /// The underlying error, for errors which did not come from the API itself.
+    #[serde(skip)]
+    source: Option<ErrorSource>,
+}
+
+/// The underlying error of an [`OpenAiError`], kept typed so that it can be turned into an [`Error`].
+#[derive(Debug, Clone)]
+enum ErrorSource {
+    Http(Arc<reqwest::Error>),
+    Deserialize(Arc<serde_json::Error>),
+    Other(Arc<dyn std::error::Error + Send + Sync>),
 }
 
 impl OpenAiError {
@@ -75,17 +377,131 @@ impl OpenAiError {
             error_type,
             param: None,
             code: None,
+            status: None,
+            extra: serde_json::Map::new(),
+            body: None,
+            source: None,
         }
     }
+
+    fn with_status(mut self, status: reqwest::StatusCode) -> Self {
+        self.status = Some(status.as_u16());
+        self
+    }
+
+    fn with_source(mut self, source: impl std::error::Error + Send + Sync + 'static) -> Self {
+        self.source = Some(ErrorSource::Other(Arc::new(source)));
+        self
+    }
+
+    /// An error for a request which could not be built, such as one with an out-of-range
+    /// parameter, returned before anything is sent to the API.
+    fn invalid_request(error: impl std::error::Error + Send + Sync + 'static) -> Self {
+        OpenAiError::new(error.to_string(), "invalid_request".to_string()).with_source(error)
+    }
+}
+
+/// Errors are compared by their contents, ignoring the underlying source.
+impl PartialEq for OpenAiError {
+    fn eq(&self, other: &Self) -> bool {
+        self.message == other.message
+            && self.error_type == other.error_type
+            && self.param == other.param
+            && self.code == other.code
+            && self.status == other.status
+            && self.extra == other.extra
+            && self.body == other.body
+    }
 }
 
+impl Eq for OpenAiError {}
+
 impl std::fmt::Display for OpenAiError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(&self.message)
     }
 }
 
-impl std::error::Error for OpenAiError {}
+impl std::error::Error for OpenAiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.source.as_ref()? {
+            ErrorSource::Http(source) => Some(source.as_ref()),
+            ErrorSource::Deserialize(source) => Some(source.as_ref()),
+            ErrorSource::Other(source) => Some(source.as_ref()),
+        }
+    }
+}
+
+/// An error from a request, distinguishing where it came from.
+///
+/// Every [`OpenAiError`] converts into this error, so `?` turns an [`ApiResponseOrError`]
+/// into a [`Result`]:
+///
+/// ```no_run
+/// use openai::{models::Model, Error};
+///
+/// # async fn run() -> openai::Result<()> {
+/// match Model::from("gpt-4o").await.map_err(Error::from) {
+///     Err(Error::Deserialize { body, source }) => eprintln!("unexpected body ({source}): {body}"),
+///     result => println!("{:?}", result?.id),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// An error response returned by the API, including those with a non-JSON body.
+    Api(OpenAiError),
+    /// A response body which did not match the expected type, such as when
+    /// the API adds a field or a compatible provider returns a different shape.
+    Deserialize {
+        body: String,
+        source: Arc<serde_json::Error>,
+    },
+    /// A failure to send the request or to receive its response.
+    Http(Arc<reqwest::Error>),
+    /// Any other error, such as an invalid request or a file that could not be read.
+    Other(OpenAiError),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl From<OpenAiError> for Error {
+    fn from(mut value: OpenAiError) -> Self {
+        match value.source.clone() {
+            Some(ErrorSource::Http(source)) => Error::Http(source),
+            Some(ErrorSource::Deserialize(source)) => Error::Deserialize {
+                body: value.body.take().unwrap_or_default(),
+                source,
+            },
+            Some(ErrorSource::Other(_)) => Error::Other(value),
+            None if value.status.is_some() => Error::Api(value),
+            None => Error::Other(value),
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Api(error) | Error::Other(error) => error.fmt(f),
+            Error::Deserialize { source, .. } => {
+                write!(f, "failed to deserialize response: {source}")
+            }
+            Error::Http(source) => source.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Api(error) | Error::Other(error) => error.source(),
+            Error::Deserialize { source, .. } => Some(source.as_ref()),
+            Error::Http(source) => Some(source.as_ref()),
+        }
+    }
+}
 
 #[derive(Deserialize, Clone)]
 #[serde(untagged)]
@@ -99,19 +515,151 @@ pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+    /// Breakdown of the tokens used in the completion, if returned by the model.
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CompletionTokensDetails {
+    /// Tokens generated by the model for reasoning, such as with o-series models.
+    pub reasoning_tokens: Option<u32>,
+    /// Audio input tokens generated by the model.
+    pub audio_tokens: Option<u32>,
+    /// When using Predicted Outputs, the number of tokens in the prediction that appeared in the completion.
+    pub accepted_prediction_tokens: Option<u32>,
+    /// When using Predicted Outputs, the number of tokens in the prediction that did not appear in the completion.
+    pub rejected_prediction_tokens: Option<u32>,
 }
 
 pub type ApiResponseOrError<T> = Result<T, OpenAiError>;
 
+/// Query parameters for paginating through list endpoints.
+#[derive(Serialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct RequestPagination {
+    /// Identifier for the last object from the previous page.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    /// Identifier for the first object from the next page, to page backwards.
+    /// Not supported by every list endpoint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    /// Number of objects to retrieve.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    /// Sort order of the objects by their creation time.
+    /// Not supported by every list endpoint.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<SortOrder>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Sampling parameters shared by completion and chat completion requests.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct SamplingParams {
+    /// What sampling temperature to use, between 0 and 2.
+    /// Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic.
+    ///
+    /// We generally recommend altering this or `top_p` but not both.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// An alternative to sampling with temperature, called nucleus sampling,
+    /// where the model considers the results of the tokens with top_p probability mass.
+    /// So 0.1 means only the tokens comprising the top 10% probability mass are considered.
+    ///
+    /// We generally recommend altering this or `temperature` but not both.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    /// Number between -2.0 and 2.0.
+    /// Positive values penalize new tokens based on whether they appear in the text so far,
+    /// increasing the model's likelihood to talk about new topics.
+    ///
+    /// [See more information about frequency and presence penalties.](https://platform.openai.com/docs/api-reference/parameter-details)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f32>,
+    /// Number between -2.0 and 2.0.
+    /// Positive values penalize new tokens based on their existing frequency in the text so far,
+    /// decreasing the model's likelihood to repeat the same line verbatim.
+    ///
+    /// [See more information about frequency and presence penalties.](https://platform.openai.com/docs/api-reference/parameter-details)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f32>,
+    /// Up to 4 sequences where the API will stop generating further tokens.
+    /// The returned text will not contain the stop sequence.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Modify the likelihood of specified tokens appearing in the completion.
+    ///
+    /// Maps tokens (specified by their token ID in the tokenizer) to an associated bias value from -100 to 100.
+    /// Mathematically, the bias is added to the logits generated by the model prior to sampling.
+    /// The exact effect will vary per model, but values between -1 and 1 should decrease or increase likelihood of selection;
+    /// values like -100 or 100 should result in a ban or exclusive selection of the relevant token.
+    ///
+    /// As an example, you can pass `{"50256": -100}` to prevent the <|endoftext|> token from being generated.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub logit_bias: HashMap<String, i16>,
+}
+
+impl SamplingParams {
+    /// Checks the parameters against the ranges accepted by the API,
+    /// naming the offending parameter in the error.
+    fn validate(&self) -> Result<(), String> {
+        let ranges = [
+            ("temperature", self.temperature, 0.0..=2.0),
+            ("top_p", self.top_p, 0.0..=1.0),
+            ("presence_penalty", self.presence_penalty, -2.0..=2.0),
+            ("frequency_penalty", self.frequency_penalty, -2.0..=2.0),
+        ];
+        for (name, value, range) in ranges {
+            if let Some(value) = value.filter(|value| !range.contains(value)) {
+                return Err(format!(
+                    "{name} must be between {} and {}, but it was set to {value}",
+                    range.start(),
+                    range.end()
+                ));
+            }
+        }
+        if let Some((token, bias)) = self
+            .logit_bias
+            .iter()
+            .find(|(_, bias)| !(-100..=100).contains(*bias))
+        {
+            return Err(format!(
+                "logit_bias values must be between -100 and 100, but token {token} was set to {bias}"
+            ));
+        }
+        if self.stop.len() > 4 {
+            return Err(format!(
+                "at most 4 stop sequences are supported, but {} were given",
+                self.stop.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
 impl From<reqwest::Error> for OpenAiError {
     fn from(value: reqwest::Error) -> Self {
-        OpenAiError::new(value.to_string(), "reqwest".to_string())
+        let mut error = OpenAiError::new(value.to_string(), "reqwest".to_string());
+        error.source = Some(ErrorSource::Http(Arc::new(value)));
+        error
+    }
+}
+
+impl From<CannotCloneRequestError> for OpenAiError {
+    fn from(value: CannotCloneRequestError) -> Self {
+        OpenAiError::new(value.to_string(), "reqwest".to_string()).with_source(value)
     }
 }
 
 impl From<std::io::Error> for OpenAiError {
     fn from(value: std::io::Error) -> Self {
-        OpenAiError::new(value.to_string(), "io".to_string())
+        OpenAiError::new(value.to_string(), "io".to_string()).with_source(value)
     }
 }
 
@@ -125,14 +673,22 @@ where
     F: FnOnce(RequestBuilder) -> RequestBuilder,
     T: DeserializeOwned,
 {
-    let api_response = openai_request(method, route, builder, credentials_opt)
-        .await?
-        .json()
-        .await?;
-    match api_response {
-        ApiResponse::Ok(t) => Ok(t),
-        ApiResponse::Err { error } => Err(error),
+    let response = openai_request(method, route, builder, credentials_opt).await?;
+    let status = response.status();
+    let body = response.text().await?;
+    if let Ok(ApiResponse::Err { error }) = serde_json::from_str::<ApiResponse<IgnoredAny>>(&body) {
+        return Err(error.with_status(status));
     }
+    serde_json::from_str(&body).map_err(|e| {
+        let mut error = OpenAiError::new(
+            format!("failed to deserialize response: {e}"),
+            "deserialize".to_string(),
+        )
+        .with_status(status);
+        error.source = Some(ErrorSource::Deserialize(Arc::new(e)));
+        error.body = Some(body);
+        error
+    })
 }
 
 async fn openai_request<F>(
@@ -144,18 +700,170 @@ async fn openai_request<F>(
 where
     F: FnOnce(RequestBuilder) -> RequestBuilder,
 {
-    let client = Client::new();
     let credentials =
         credentials_opt.unwrap_or_else(|| DEFAULT_CREDENTIALS.read().unwrap().clone());
-    let mut request = client.request(method, format!("{}{route}", credentials.base_url));
+    let mut request = credentials
+        .client()
+        .request(method, format!("{}{route}", credentials.base_url));
     request = builder(request);
-    let response = request
-        .header(AUTHORIZATION, format!("Bearer {}", credentials.api_key))
-        .send()
-        .await?;
+    let response = authorize(request, &credentials).send().await?;
     Ok(response)
 }
 
+/// Sends an authorized request to the API and returns the raw response,
+/// without checking its status or deserializing its body.
+///
+/// This is an escape hatch for endpoints or response bodies that are not modelled by the crate.
+/// The `builder` closure may be used to set the request body, query parameters or headers.
+///
+/// ## Examples
+///
+/// ```no_run
+/// use openai::{openai_request_raw, Credentials};
+/// use reqwest::Method;
+///
+/// # async fn run() -> openai::ApiResponseOrError<()> {
+/// let credentials = Credentials::from_env();
+/// let response = openai_request_raw(Method::GET, "models", |r| r, credentials).await?;
+/// println!("{}: {}", response.status(), response.text().await?);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn openai_request_raw<F>(
+    method: Method,
+    route: &str,
+    builder: F,
+    credentials: Credentials,
+) -> ApiResponseOrError<Response>
+where
+    F: FnOnce(RequestBuilder) -> RequestBuilder,
+{
+    openai_request(method, route, builder, Some(credentials)).await
+}
+
+/// Sends a JSON request to any API route and returns the JSON response, for endpoints
+/// or parameters that are not modeled by this crate yet.
+/// A `body` of `Value::Null` sends no body.
+///
+/// Unlike [`openai_request_raw`], error responses are parsed into an [`OpenAiError`]
+/// like for every other request.
+///
+/// ## Examples
+///
+/// ```no_run
+/// use openai::{openai_request_value, Credentials};
+/// use reqwest::Method;
+/// use serde_json::json;
+///
+/// # async fn run() -> openai::ApiResponseOrError<()> {
+/// let credentials = Credentials::from_env();
+/// let body = json!({ "model": "gpt-4o-mini", "input": "Hello!" });
+/// let response = openai_request_value(Method::POST, "responses", body, credentials).await?;
+/// println!("{}", response["output"]);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn openai_request_value(
+    method: Method,
+    route: &str,
+    body: Value,
+    credentials: Credentials,
+) -> ApiResponseOrError<Value> {
+    openai_request_json(
+        method,
+        route,
+        |request| match body {
+            Value::Null => request,
+            body => request.json(&body),
+        },
+        Some(credentials),
+    )
+    .await
+}
+
+/// Same as [`openai_request_value`], but for a streaming endpoint.
+/// A `body` of `Value::Null` sends no body.
+/// The data of each server-sent event is parsed as JSON and sent to the receiver,
+/// until the stream ends or sends `[DONE]`.
+pub async fn openai_request_value_stream(
+    method: Method,
+    route: &str,
+    body: Value,
+    credentials: Credentials,
+) -> Result<Receiver<Value>, CannotCloneRequestError> {
+    let stream = openai_request_stream(
+        method,
+        route,
+        |request| match body {
+            Value::Null => request,
+            body => request.json(&body),
+        },
+        Some(credentials),
+    )
+    .await?;
+    let (tx, rx) = channel::<Value>(32);
+    tokio::spawn(forward_value_stream(stream, tx));
+    Ok(rx)
+}
+
+async fn forward_value_stream(mut stream: EventSource, tx: Sender<Value>) -> anyhow::Result<()> {
+    loop {
+        let event = tokio::select! {
+            event = stream.next() => event,
+            _ = tx.closed() => break,
+        };
+        let Some(event) = event else {
+            break;
+        };
+        match event? {
+            Event::Message(event) if event.data == "[DONE]" => break,
+            Event::Message(event) => tx.send(serde_json::from_str(&event.data)?).await?,
+            Event::Open => {}
+        }
+    }
+    stream.close();
+    Ok(())
+}
+
+/// Sends a request, turning an unsuccessful response status into an error.
+async fn openai_request_ok<F>(
+    method: Method,
+    route: &str,
+    builder: F,
+    credentials_opt: Option<Credentials>,
+) -> ApiResponseOrError<Response>
+where
+    F: FnOnce(RequestBuilder) -> RequestBuilder,
+{
+    let response = openai_request(method, route, builder, credentials_opt).await?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await?;
+    match serde_json::from_str::<ApiResponse<Value>>(&body) {
+        Ok(ApiResponse::Err { error }) => Err(error.with_status(status)),
+        _ => Err(
+            OpenAiError::new(format!("{status}: {body}"), "http".to_string()).with_status(status),
+        ),
+    }
+}
+
+/// Sends a request and returns the raw response body,
+/// for endpoints which respond with binary content rather than JSON.
+async fn openai_request_bytes<F>(
+    method: Method,
+    route: &str,
+    builder: F,
+    credentials_opt: Option<Credentials>,
+) -> ApiResponseOrError<Bytes>
+where
+    F: FnOnce(RequestBuilder) -> RequestBuilder,
+{
+    let response = openai_request_ok(method, route, builder, credentials_opt).await?;
+    Ok(response.bytes().await?)
+}
+
 async fn openai_request_stream<F>(
     method: Method,
     route: &str,
@@ -165,17 +873,32 @@ async fn openai_request_stream<F>(
 where
     F: FnOnce(RequestBuilder) -> RequestBuilder,
 {
-    let client = Client::new();
     let credentials =
         credentials_opt.unwrap_or_else(|| DEFAULT_CREDENTIALS.read().unwrap().clone());
-    let mut request = client.request(method, format!("{}{route}", credentials.base_url));
+    let mut request = credentials
+        .client()
+        .request(method, format!("{}{route}", credentials.base_url));
     request = builder(request);
-    let stream = request
-        .header(AUTHORIZATION, format!("Bearer {}", credentials.api_key))
-        .eventsource()?;
+    let stream = authorize(request, &credentials).eventsource()?;
     Ok(stream)
 }
 
+/// Applies the authorization and any extra headers from the credentials.
+fn authorize(mut request: RequestBuilder, credentials: &Credentials) -> RequestBuilder {
+    request = match &credentials.flavor {
+        ApiFlavor::OpenAi => {
+            request.header(AUTHORIZATION, format!("Bearer {}", credentials.api_key))
+        }
+        ApiFlavor::Azure { api_version } => request
+            .header("api-key", &credentials.api_key)
+            .query(&[("api-version", api_version)]),
+    };
+    for (name, value) in &credentials.extra_headers {
+        request = request.header(name, value);
+    }
+    request
+}
+
 async fn openai_get<T>(route: &str, credentials_opt: Option<Credentials>) -> ApiResponseOrError<T>
 where
     T: DeserializeOwned,
@@ -228,6 +951,76 @@ where
     .await
 }
 
+/// The model for a request that does not set one: the default model of its credentials,
+/// or else the `OPENAI_DEFAULT_MODEL` environment variable.
+fn default_model(credentials: Option<&Credentials>) -> Result<String, String> {
+    default_model_from_vars(credentials, |name| env::var(name))
+}
+
+fn default_model_from_vars(
+    credentials: Option<&Credentials>,
+    var: impl Fn(&str) -> Result<String, VarError>,
+) -> Result<String, String> {
+    credentials
+        .and_then(|credentials| credentials.default_model.clone())
+        .or_else(|| var("OPENAI_DEFAULT_MODEL").ok())
+        .ok_or_else(|| {
+            "no model was set, and neither the credentials nor OPENAI_DEFAULT_MODEL provide a default"
+                .to_string()
+        })
+}
+
+/// The given credentials, or the default ones if unset, sending requests through `client`.
+fn credentials_with_client(credentials_opt: Option<Credentials>, client: &Client) -> Credentials {
+    credentials_opt
+        .unwrap_or_else(|| DEFAULT_CREDENTIALS.read().unwrap().clone())
+        .with_client(client.clone())
+}
+
+/// Infers the mime type of a file from its extension,
+/// defaulting to `application/octet-stream` when unknown.
+fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "flac" => "audio/flac",
+        "mp3" | "mpga" | "mpeg" => "audio/mpeg",
+        "mp4" | "m4a" => "audio/mp4",
+        "ogg" | "oga" => "audio/ogg",
+        "wav" => "audio/wav",
+        "webm" => "audio/webm",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "jsonl" => "application/jsonl",
+        "json" => "application/json",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Opens a local file as a streamed multipart part, named after the file
+/// and labeled with the mime type of its extension.
+async fn file_part(file_path: &str) -> ApiResponseOrError<Part> {
+    let path = Path::new(file_path).canonicalize()?;
+    let mime = mime_type(&path);
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let file = tokio::fs::File::open(path).await?;
+    let part = Part::stream(file).file_name(file_name).mime_str(mime)?;
+    Ok(part)
+}
+
 /// Sets the key for all OpenAI API functions.
 ///
 /// ## Examples
@@ -278,7 +1071,11 @@ pub fn set_base_url(mut value: String) {
     credentials.base_url = value;
 }
 
-fn parse_base_url(mut value: String) -> String {
+fn parse_base_url(value: String) -> String {
+    let mut value = value.trim().to_string();
+    if value.is_empty() {
+        return DEFAULT_BASE_URL.clone();
+    }
     if !value.ends_with('/') {
         value += "/";
     }
@@ -287,5 +1084,597 @@ fn parse_base_url(mut value: String) -> String {
 
 #[cfg(test)]
 pub mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::task::JoinHandle;
+
     pub const DEFAULT_LEGACY_MODEL: &str = "gpt-3.5-turbo-instruct";
+
+    /// Serves a single canned HTTP response on a local port.
+    /// Returns credentials pointing at the server and a handle resolving to the raw request received.
+    pub async fn mock_server(status: u16, body: &str) -> (Credentials, JoinHandle<String>) {
+        let head = format!(
+            "HTTP/1.1 {status} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            body.len()
+        );
+        mock_server_writes(vec![head.into_bytes(), body.as_bytes().to_vec()]).await
+    }
+
+    /// Serves a server-sent events response, writing each chunk separately
+    /// so that the client receives them as distinct reads.
+    pub async fn mock_event_stream(chunks: Vec<Vec<u8>>) -> (Credentials, JoinHandle<String>) {
+        let head =
+            "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n";
+        let mut writes = vec![head.as_bytes().to_vec()];
+        writes.extend(chunks);
+        mock_server_writes(writes).await
+    }
+
+    pub async fn mock_server_writes(writes: Vec<Vec<u8>>) -> (Credentials, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let handle = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            serve_connection(socket, writes).await
+        });
+        let credentials = Credentials::new("sk-mock", format!("http://{address}/v1/"));
+        (credentials, handle)
+    }
+
+    /// Serves the same canned JSON response to `count` concurrent connections.
+    /// The handle resolves to the raw requests received, in the order they were accepted.
+    pub async fn mock_server_connections(
+        count: usize,
+        status: u16,
+        body: &str,
+    ) -> (Credentials, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let head = format!(
+            "HTTP/1.1 {status} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            body.len()
+        );
+        let writes = vec![head.into_bytes(), body.as_bytes().to_vec()];
+        let handle = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            for _ in 0..count {
+                let (socket, _) = listener.accept().await.unwrap();
+                connections.push(tokio::spawn(serve_connection(socket, writes.clone())));
+            }
+            let mut requests = Vec::new();
+            for connection in connections {
+                requests.push(connection.await.unwrap());
+            }
+            requests
+        });
+        let credentials = Credentials::new("sk-mock", format!("http://{address}/v1/"));
+        (credentials, handle)
+    }
+
+    /// Streams `chunks` as server-sent events, then keeps the connection open without
+    /// sending anything else. The handle resolves once the client closes the connection.
+    pub async fn mock_stalled_event_stream(chunks: Vec<Vec<u8>>) -> (Credentials, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            read_request(&mut socket).await;
+            let head = "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\r\n".as_bytes();
+            socket.write_all(head).await.unwrap();
+            for chunk in chunks {
+                socket.write_all(&chunk).await.unwrap();
+            }
+            let mut buffer = [0u8; 4096];
+            while let Ok(read) = socket.read(&mut buffer).await {
+                if read == 0 {
+                    break;
+                }
+            }
+        });
+        let credentials = Credentials::new("sk-mock", format!("http://{address}/v1/"));
+        (credentials, handle)
+    }
+
+    async fn read_request(socket: &mut TcpStream) -> Vec<u8> {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = socket.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            if read == 0 || is_complete_request(&request) {
+                break;
+            }
+        }
+        request
+    }
+
+    async fn serve_connection(mut socket: TcpStream, writes: Vec<Vec<u8>>) -> String {
+        socket.set_nodelay(true).unwrap();
+        let request = read_request(&mut socket).await;
+        for write in writes {
+            if socket.write_all(&write).await.is_err() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        socket.shutdown().await.ok();
+        String::from_utf8_lossy(&request).into_owned()
+    }
+
+    fn is_complete_request(request: &[u8]) -> bool {
+        let text = String::from_utf8_lossy(request);
+        let Some(header_end) = text.find("\r\n\r\n") else {
+            return false;
+        };
+        let headers = text[..header_end].to_ascii_lowercase();
+        if headers.contains("transfer-encoding: chunked") {
+            return request.ends_with(b"0\r\n\r\n");
+        }
+        let content_length = text[..header_end]
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+        request.len() >= header_end + 4 + content_length
+    }
+
+    #[test]
+    fn pagination_query() {
+        let pagination = RequestPagination {
+            after: Some("obj_1".to_string()),
+            before: Some("obj_9".to_string()),
+            limit: Some(20),
+            order: Some(SortOrder::Desc),
+        };
+        let request = Client::new()
+            .get("https://api.openai.com/v1/chat/completions?model=gpt-4o")
+            .query(&pagination)
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            request.url().query(),
+            Some("model=gpt-4o&after=obj_1&before=obj_9&limit=20&order=desc")
+        );
+        let request = Client::new()
+            .get("https://api.openai.com/v1/batches")
+            .query(&RequestPagination::default())
+            .build()
+            .unwrap();
+        assert_eq!(request.url().query(), None);
+    }
+
+    #[test]
+    fn credentials_from_vars() {
+        let vars = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+                    .ok_or(VarError::NotPresent)
+            }
+        };
+
+        let credentials = Credentials::from_vars(vars(&[
+            ("OPENAI_KEY", "sk-env"),
+            ("OPENAI_BASE_URL", "http://localhost:8080/v1"),
+        ]))
+        .unwrap();
+        assert_eq!(credentials.api_key(), "sk-env");
+        assert_eq!(credentials.base_url(), "http://localhost:8080/v1/");
+
+        let missing_key = vars(&[("OPENAI_BASE_URL", "http://localhost:8080/v1")]);
+        assert_eq!(
+            Credentials::from_vars(missing_key),
+            Err(VarError::NotPresent)
+        );
+        let default = Credentials::default_from_vars(missing_key);
+        assert_eq!(default.api_key(), "");
+        assert_eq!(default.base_url(), "http://localhost:8080/v1/");
+
+        let default = Credentials::default_from_vars(vars(&[]));
+        assert_eq!(default.base_url(), DEFAULT_BASE_URL.as_str());
+    }
+
+    #[test]
+    fn mime_type_from_extension() {
+        let mime = |file: &str| mime_type(Path::new(file));
+        assert_eq!(mime("speech.mp3"), "audio/mpeg");
+        assert_eq!(mime("recordings/Speech.M4A"), "audio/mp4");
+        assert_eq!(mime("speech.wav"), "audio/wav");
+        assert_eq!(mime("batch.jsonl"), "application/jsonl");
+        assert_eq!(mime("docs/Report.PDF"), "application/pdf");
+        assert_eq!(mime("photo.jpeg"), "image/jpeg");
+        assert_eq!(mime("speech"), "application/octet-stream");
+        assert_eq!(mime("archive.zip"), "application/octet-stream");
+    }
+
+    #[test]
+    fn default_model_from_vars() {
+        let env_model = |name: &str| match name {
+            "OPENAI_DEFAULT_MODEL" => Ok("gpt-4o-mini".to_string()),
+            _ => Err(VarError::NotPresent),
+        };
+        let unset = |_: &str| Err(VarError::NotPresent);
+        let credentials = Credentials::new("sk-test", "").with_default_model("gpt-4.1");
+
+        assert_eq!(
+            super::default_model_from_vars(Some(&credentials), env_model).unwrap(),
+            "gpt-4.1"
+        );
+        assert_eq!(
+            super::default_model_from_vars(None, env_model).unwrap(),
+            "gpt-4o-mini"
+        );
+        let error = super::default_model_from_vars(None, unset).unwrap_err();
+        assert!(error.contains("OPENAI_DEFAULT_MODEL"));
+    }
+
+    #[test]
+    fn credentials_from_vars_prefixed() {
+        let vars = |name: &str| match name {
+            "GROQ_OPENAI_KEY" => Ok("gsk-prefixed".to_string()),
+            "GROQ_OPENAI_BASE_URL" => Ok("https://api.groq.com/openai/v1".to_string()),
+            "OPENAI_KEY" => Ok("sk-unprefixed".to_string()),
+            _ => Err(VarError::NotPresent),
+        };
+
+        let credentials = Credentials::from_vars_prefixed("GROQ_", vars).unwrap();
+        assert_eq!(credentials.api_key(), "gsk-prefixed");
+        assert_eq!(credentials.base_url(), "https://api.groq.com/openai/v1/");
+        assert_eq!(
+            Credentials::from_vars_prefixed("MISSING_", vars),
+            Err(VarError::NotPresent)
+        );
+    }
+
+    #[test]
+    fn credentials_from_file() {
+        let credentials = Credentials::from_file("test_data/credentials.env").unwrap();
+        assert_eq!(credentials.api_key(), "sk-from-file");
+        assert_eq!(credentials.base_url(), "http://localhost:11434/v1/");
+        assert_eq!(credentials.default_model(), Some("llama3.2"));
+
+        let error = Credentials::from_file("test_data/file_upload_test1.jsonl").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn raw_request() {
+        let (credentials, server) = mock_server(200, r#"{"raw":true}"#).await;
+        let response = openai_request_raw(Method::GET, "models", |r| r, credentials)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.bytes().await.unwrap().as_ref(), br#"{"raw":true}"#);
+        assert!(server.await.unwrap().starts_with("GET /v1/models "));
+    }
+
+    #[tokio::test]
+    async fn value_request() {
+        let (credentials, server) = mock_server(200, r#"{"id":"resp_123"}"#).await;
+        let response = openai_request_value(
+            Method::POST,
+            "responses",
+            serde_json::json!({ "input": "Hello!" }),
+            credentials,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response["id"], "resp_123");
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /v1/responses "));
+        assert!(request.ends_with(r#"{"input":"Hello!"}"#));
+
+        let (credentials, _server) = mock_server(
+            404,
+            r#"{"error":{"message":"Not found","type":"invalid_request_error","param":null,"code":null}}"#,
+        )
+        .await;
+        let error = openai_request_value(Method::GET, "unknown", Value::Null, credentials)
+            .await
+            .unwrap_err();
+        assert_eq!(error.message, "Not found");
+        assert_eq!(error.status, Some(404));
+    }
+
+    #[tokio::test]
+    async fn value_stream() {
+        let chunks = vec![
+            b"data: {\"delta\":\"Hi\"}\n\n".to_vec(),
+            b"data: {\"delta\":\"!\"}\n\n".to_vec(),
+            b"data: [DONE]\n\n".to_vec(),
+        ];
+        let (credentials, _server) = mock_event_stream(chunks).await;
+        let mut stream = openai_request_value_stream(
+            Method::POST,
+            "responses",
+            serde_json::json!({ "stream": true }),
+            credentials,
+        )
+        .await
+        .unwrap();
+
+        let mut deltas = Vec::new();
+        while let Some(event) = stream.recv().await {
+            deltas.push(event["delta"].as_str().unwrap().to_string());
+        }
+        assert_eq!(deltas, ["Hi", "!"]);
+
+        let (credentials, server) = mock_event_stream(vec![b"data: [DONE]\n\n".to_vec()]).await;
+        let mut stream = openai_request_value_stream(
+            Method::GET,
+            "responses/resp_123",
+            Value::Null,
+            credentials,
+        )
+        .await
+        .unwrap();
+        assert!(stream.recv().await.is_none());
+        let request = server.await.unwrap();
+        assert!(request.starts_with("GET /v1/responses/resp_123 "));
+        assert!(request.ends_with("\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn extra_headers() {
+        let (credentials, server) = mock_server(200, "{}").await;
+        let credentials = credentials.with_header("X-Trace-Id", "trace-123");
+        openai_get::<Value>("models", Some(credentials))
+            .await
+            .unwrap();
+        let request = server.await.unwrap().to_lowercase();
+        assert!(request.contains("x-trace-id: trace-123"));
+        assert!(request.contains("authorization: bearer sk-mock"));
+    }
+
+    #[test]
+    fn reasoning_usage() {
+        let usage: Usage = serde_json::from_value(serde_json::json!({
+            "prompt_tokens": 9,
+            "completion_tokens": 1046,
+            "total_tokens": 1055,
+            "prompt_tokens_details": { "cached_tokens": 0 },
+            "completion_tokens_details": {
+                "reasoning_tokens": 1024,
+                "accepted_prediction_tokens": 0,
+                "rejected_prediction_tokens": 0,
+            },
+        }))
+        .unwrap();
+
+        let details = usage.completion_tokens_details.unwrap();
+        assert_eq!(details.reasoning_tokens, Some(1024));
+        assert_eq!(details.audio_tokens, None);
+    }
+
+    #[test]
+    fn empty_base_url() {
+        let credentials = Credentials::new("sk-test", "");
+        assert_eq!(credentials.base_url(), DEFAULT_BASE_URL.as_str());
+        let credentials = Credentials::try_new("sk-test", "").unwrap();
+        assert_eq!(credentials.base_url(), DEFAULT_BASE_URL.as_str());
+    }
+
+    #[test]
+    fn whitespace_base_url() {
+        let credentials = Credentials::new("sk-test", " \t\n");
+        assert_eq!(credentials.base_url(), DEFAULT_BASE_URL.as_str());
+        let credentials = Credentials::try_new("sk-test", "  http://localhost:8080/v1 ").unwrap();
+        assert_eq!(credentials.base_url(), "http://localhost:8080/v1/");
+    }
+
+    #[test]
+    fn malformed_base_url() {
+        for base_url in [
+            "not a url",
+            "localhost:8080",
+            "ftp://example.com",
+            "http://",
+        ] {
+            let error = Credentials::try_new("sk-test", base_url).unwrap_err();
+            assert_eq!(error.error_type, "invalid_base_url", "{base_url}");
+        }
+    }
+
+    #[test]
+    fn provider_error_extra_fields() {
+        let response: ApiResponse<Value> = serde_json::from_value(serde_json::json!({
+            "error": {
+                "message": "The response was filtered due to the prompt triggering Azure OpenAI's content management policy.",
+                "type": "invalid_request_error",
+                "param": "prompt",
+                "code": "content_filter",
+                "status": 400,
+                "innererror": {
+                    "code": "ResponsibleAIPolicyViolation",
+                    "content_filter_result": {
+                        "hate": { "filtered": true, "severity": "high" },
+                    },
+                },
+            },
+        }))
+        .unwrap();
+        let ApiResponse::Err { error } = response else {
+            panic!("expected an error response");
+        };
+
+        assert_eq!(error.code.as_deref(), Some("content_filter"));
+        assert_eq!(error.extra["status"], 400);
+        assert_eq!(
+            error.extra["innererror"]["code"],
+            "ResponsibleAIPolicyViolation"
+        );
+        assert_eq!(
+            error.extra["innererror"]["content_filter_result"]["hate"]["filtered"],
+            true
+        );
+    }
+
+    #[tokio::test]
+    async fn error_status() {
+        let body = serde_json::json!({
+            "error": {
+                "message": "Rate limit reached for requests",
+                "type": "requests",
+                "param": null,
+                "code": "rate_limit_exceeded",
+            },
+        })
+        .to_string();
+        let (credentials, _server) = mock_server(429, &body).await;
+        let error = openai_get::<Value>("models", Some(credentials))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status, Some(429));
+        assert_eq!(error.code.as_deref(), Some("rate_limit_exceeded"));
+
+        let (credentials, _server) = mock_server(401, "Unauthorized").await;
+        let error = openai_request_bytes(
+            Method::GET,
+            "files/file-abc/content",
+            |r| r,
+            Some(credentials),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(error.status, Some(401));
+        assert_eq!(error.error_type, "http");
+        assert!(matches!(Error::from(error), Error::Api(error) if error.status == Some(401)));
+    }
+
+    #[tokio::test]
+    async fn deserialize_error_body() {
+        let body = r#"{"object": "list", "models": []}"#;
+        let (credentials, _server) = mock_server(200, body).await;
+        let error = openai_get::<Vec<String>>("models", Some(credentials))
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.error_type, "deserialize");
+        assert!(error.message.contains("expected a sequence"));
+        assert_eq!(error.status, Some(200));
+        assert_eq!(error.body.as_deref(), Some(body));
+        let source = std::error::Error::source(&error).unwrap();
+        assert!(source.is::<serde_json::Error>());
+
+        match Error::from(error) {
+            Error::Deserialize {
+                body: error_body,
+                source,
+            } => {
+                assert_eq!(error_body, body);
+                assert!(source.is_data());
+            }
+            error => panic!("expected a deserialize error, got {error:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_client() {
+        let (credentials, server) = mock_server(200, r#"{"object": "list", "data": []}"#).await;
+        let client = Client::builder()
+            .user_agent("corporate-proxy/1.0")
+            .build()
+            .unwrap();
+        let credentials = credentials.with_client(client);
+        let _: Value = openai_get("models", Some(credentials)).await.unwrap();
+
+        let request = server.await.unwrap().to_lowercase();
+        assert!(request.contains("user-agent: corporate-proxy/1.0"));
+        assert!(request.contains("authorization: bearer sk-mock"));
+    }
+
+    #[tokio::test]
+    async fn azure_deployment() {
+        let (credentials, server) = mock_server(200, "{}").await;
+        let endpoint = credentials.base_url().trim_end_matches("v1/");
+        let credentials = Credentials::azure("azure-key", endpoint, "gpt-4o-prod", "2024-10-21");
+        let _: Value = openai_post(
+            "chat/completions",
+            &serde_json::json!({ "messages": [] }),
+            Some(credentials),
+        )
+        .await
+        .unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with(
+            "POST /openai/deployments/gpt-4o-prod/chat/completions?api-version=2024-10-21 "
+        ));
+        let request = request.to_lowercase();
+        assert!(request.contains("api-key: azure-key"));
+        assert!(!request.contains("authorization"));
+    }
+
+    #[tokio::test]
+    async fn with_api_key() {
+        let (credentials, server) = mock_server_connections(2, 200, "{}").await;
+        let client = Client::builder().user_agent("tenant-pool").build().unwrap();
+        let tenant_a = credentials.with_client(client).with_api_key("sk-tenant-a");
+        let tenant_b = tenant_a.with_api_key("sk-tenant-b");
+        let _: Value = openai_get("models", Some(tenant_a)).await.unwrap();
+        let _: Value = openai_get("models", Some(tenant_b)).await.unwrap();
+
+        let requests: Vec<_> = server
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|request| request.to_lowercase())
+            .collect();
+        assert!(requests[0].contains("authorization: bearer sk-tenant-a"));
+        assert!(requests[1].contains("authorization: bearer sk-tenant-b"));
+        // Both requests went through the same client.
+        assert!(requests
+            .iter()
+            .all(|request| request.contains("user-agent: tenant-pool")));
+    }
+
+    #[tokio::test]
+    async fn per_request_api_key_override() {
+        let (default, server) = mock_server_connections(3, 200, "{}").await;
+        let _: Value = openai_get("models", Some(default.clone())).await.unwrap();
+        let _: Value = openai_get("models", Some(default.with_api_key("sk-tenant")))
+            .await
+            .unwrap();
+        let _: Value = openai_get("models", Some(default)).await.unwrap();
+
+        let requests: Vec<_> = server
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|request| request.to_lowercase())
+            .collect();
+        assert!(requests[0].contains("authorization: bearer sk-mock"));
+        assert!(requests[1].contains("authorization: bearer sk-tenant"));
+        assert!(requests[2].contains("authorization: bearer sk-mock"));
+    }
+
+    #[tokio::test]
+    async fn transport_error_source() {
+        // Bind to find a free port, then close it so that the connection is refused.
+        let address = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let credentials = Credentials::new("sk-mock", format!("http://{address}/"));
+        let error = openai_get::<Value>("models", Some(credentials))
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.error_type, "reqwest");
+        let source = std::error::Error::source(&error).unwrap();
+        assert!(source
+            .downcast_ref::<reqwest::Error>()
+            .unwrap()
+            .is_connect());
+
+        match Error::from(error) {
+            Error::Http(source) => assert!(source.is_connect()),
+            error => panic!("expected an HTTP error, got {error:?}"),
+        }
+    }
 }
@@ -4,6 +4,8 @@
 
 use super::{openai_get, ApiResponseOrError, Credentials};
 use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
 
 #[derive(Deserialize, Clone)]
 pub struct Model {
@@ -11,6 +13,14 @@ pub struct Model {
     pub object: String,
     pub created: u32,
     pub owned_by: String,
+    /// Any other fields of the model, such as those returned by OpenAI-compatible providers.
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct ModelList {
+    data: Vec<Model>,
 }
 
 #[derive(Deserialize, Clone)]
@@ -41,13 +51,79 @@ impl Model {
     pub async fn fetch(id: &str, credentials: Credentials) -> ApiResponseOrError<Self> {
         openai_get(&format!("models/{id}"), Some(credentials)).await
     }
+
+    /// Checks whether a model exists and is available with these credentials,
+    /// distinguishing an unknown model from other errors, such as network failures.
+    pub async fn exists(id: &str, credentials: Credentials) -> ApiResponseOrError<bool> {
+        match Model::fetch(id, credentials).await {
+            Ok(_) => Ok(true),
+            Err(error) if error.code.as_deref() == Some("model_not_found") => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Lists the currently available models.
+    pub async fn list(credentials: Credentials) -> ApiResponseOrError<Vec<Self>> {
+        let list: ModelList = openai_get("models", Some(credentials)).await?;
+        Ok(list.data)
+    }
+
+    /// Lists the available models which can be used with chat completions.
+    /// See [`Model::is_chat_model`] for how they are identified.
+    pub async fn list_chat_models(credentials: Credentials) -> ApiResponseOrError<Vec<Self>> {
+        let mut models = Model::list(credentials).await?;
+        models.retain(Model::is_chat_model);
+        Ok(models)
+    }
+
+    /// Lists the available models which can be used with embeddings.
+    /// See [`Model::is_embedding_model`] for how they are identified.
+    pub async fn list_embedding_models(credentials: Credentials) -> ApiResponseOrError<Vec<Self>> {
+        let mut models = Model::list(credentials).await?;
+        models.retain(Model::is_embedding_model);
+        Ok(models)
+    }
+
+    /// Whether this model can be used with chat completions, judging by its ID,
+    /// since the API does not report model capabilities.
+    /// This follows OpenAI's naming, including fine-tuned models,
+    /// and may not recognize models from other providers.
+    pub fn is_chat_model(&self) -> bool {
+        const CHAT_PREFIXES: [&str; 5] = ["gpt-", "chatgpt-", "o1", "o3", "o4"];
+        const OTHER_ENDPOINTS: [&str; 7] = [
+            "instruct",
+            "realtime",
+            "audio",
+            "transcribe",
+            "tts",
+            "image",
+            "search",
+        ];
+        let id = self.base_id();
+        CHAT_PREFIXES.iter().any(|prefix| id.starts_with(prefix))
+            && !OTHER_ENDPOINTS.iter().any(|part| id.contains(part))
+    }
+
+    /// Whether this model can be used with embeddings, judging by its ID.
+    pub fn is_embedding_model(&self) -> bool {
+        self.base_id().contains("embedding")
+    }
+
+    /// The ID of the model a fine-tuned model is based on, or the ID itself otherwise.
+    fn base_id(&self) -> &str {
+        match self.id.strip_prefix("ft:") {
+            Some(fine_tuned) => fine_tuned.split(':').next().unwrap_or_default(),
+            None => &self.id,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tests::DEFAULT_LEGACY_MODEL;
+    use crate::tests::{mock_server, mock_server_connections, DEFAULT_LEGACY_MODEL};
     use dotenvy::dotenv;
+    use serde_json::json;
 
     #[tokio::test]
     async fn model() {
@@ -58,4 +134,82 @@ mod tests {
             .unwrap();
         assert_eq!(model.id, DEFAULT_LEGACY_MODEL);
     }
+
+    #[tokio::test]
+    async fn model_exists() {
+        let (credentials, _server) = mock_server(
+            200,
+            r#"{"id": "gpt-4o", "object": "model", "created": 1715367049, "owned_by": "system"}"#,
+        )
+        .await;
+        assert!(Model::exists("gpt-4o", credentials).await.unwrap());
+
+        let (credentials, _server) = mock_server(
+            404,
+            r#"{"error": {"message": "The model `gpt-5o` does not exist", "type": "invalid_request_error", "param": null, "code": "model_not_found"}}"#,
+        )
+        .await;
+        assert!(!Model::exists("gpt-5o", credentials).await.unwrap());
+
+        let (credentials, _server) = mock_server(
+            401,
+            r#"{"error": {"message": "Incorrect API key provided", "type": "invalid_request_error", "param": null, "code": "invalid_api_key"}}"#,
+        )
+        .await;
+        let error = Model::exists("gpt-4o", credentials).await.unwrap_err();
+        assert_eq!(error.code.as_deref(), Some("invalid_api_key"));
+    }
+
+    #[test]
+    fn unknown_model_fields() {
+        let model: Model = serde_json::from_value(json!({
+            "id": "llama-3.1-8b-instant",
+            "object": "model",
+            "created": 1693721698,
+            "owned_by": "Meta",
+            "active": true,
+            "context_window": 131072,
+        }))
+        .unwrap();
+
+        assert_eq!(model.extra["context_window"], 131072);
+        assert_eq!(model.extra["active"], true);
+    }
+
+    #[tokio::test]
+    async fn list_chat_models() {
+        let model = |id: &str| json!({ "id": id, "object": "model", "created": 1715367049, "owned_by": "system" });
+        let ids = [
+            "gpt-4o",
+            "gpt-3.5-turbo-instruct",
+            "text-embedding-3-small",
+            "text-embedding-ada-002",
+            "tts-1",
+            "whisper-1",
+            "omni-moderation-latest",
+            "dall-e-3",
+            "o3-mini",
+            "gpt-4o-realtime-preview",
+            "ft:gpt-4o-mini-2024-07-18:org::abc123",
+        ];
+        let body = json!({ "object": "list", "data": ids.map(model) }).to_string();
+        let (credentials, server) = mock_server_connections(2, 200, &body).await;
+
+        let chat_models = Model::list_chat_models(credentials.clone()).await.unwrap();
+        let embedding_models = Model::list_embedding_models(credentials).await.unwrap();
+
+        let chat_ids: Vec<_> = chat_models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(
+            chat_ids,
+            ["gpt-4o", "o3-mini", "ft:gpt-4o-mini-2024-07-18:org::abc123"]
+        );
+        let embedding_ids: Vec<_> = embedding_models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(
+            embedding_ids,
+            ["text-embedding-3-small", "text-embedding-ada-002"]
+        );
+        for request in server.await.unwrap() {
+            assert!(request.starts_with("GET /v1/models "));
+        }
+    }
 }
@@ -30,6 +30,19 @@ pub struct Categories {
     pub violence: bool,
     #[serde(rename = "violence/graphic")]
     pub violence_graphic: bool,
+    /// The categories below are only returned by newer models, and are `false` when absent.
+    #[serde(default)]
+    pub harassment: bool,
+    #[serde(default, rename = "harassment/threatening")]
+    pub harassment_threatening: bool,
+    #[serde(default, rename = "self-harm/intent")]
+    pub self_harm_intent: bool,
+    #[serde(default, rename = "self-harm/instructions")]
+    pub self_harm_instructions: bool,
+    #[serde(default)]
+    pub illicit: bool,
+    #[serde(default, rename = "illicit/violent")]
+    pub illicit_violent: bool,
 }
 
 #[derive(Deserialize, Clone, Debug)]
@@ -45,6 +58,17 @@ pub struct CategoryScores {
     pub violence: f64,
     #[serde(rename = "violence/graphic")]
     pub violence_graphic: f64,
+    /// The scores below are only returned by newer models.
+    pub harassment: Option<f64>,
+    #[serde(rename = "harassment/threatening")]
+    pub harassment_threatening: Option<f64>,
+    #[serde(rename = "self-harm/intent")]
+    pub self_harm_intent: Option<f64>,
+    #[serde(rename = "self-harm/instructions")]
+    pub self_harm_instructions: Option<f64>,
+    pub illicit: Option<f64>,
+    #[serde(rename = "illicit/violent")]
+    pub illicit_violent: Option<f64>,
 }
 
 #[derive(Serialize, Builder, Debug, Clone)]
@@ -52,12 +76,13 @@ pub struct CategoryScores {
 #[builder(name = "ModerationBuilder")]
 #[builder(setter(strip_option, into))]
 pub struct ModerationRequest {
-    /// The input text to classify.
-    pub input: String,
-    /// ID of the model to use.
-    /// Two content moderations models are available: `text-moderation-stable` and `text-moderation-latest`.
+    /// The input to classify: a single text, multiple texts,
+    /// or, for `omni-moderation-latest`, an array of text and image parts.
+    pub input: ModerationInput,
+    /// ID of the model to use. Defaults to `omni-moderation-latest`.
+    /// The legacy `text-moderation-stable` and `text-moderation-latest` models are also available.
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[builder(default)]
+    #[builder(default = "Some(String::from(\"omni-moderation-latest\"))")]
     pub model: Option<String>,
     /// The credentials to use for this request.
     #[serde(skip_serializing)]
@@ -65,12 +90,79 @@ pub struct ModerationRequest {
     pub credentials: Option<Credentials>,
 }
 
+/// The input to a moderation request.
+/// There is one result for each text, or one for all the parts together.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ModerationInput {
+    Text(String),
+    Texts(Vec<String>),
+    /// Text and image parts, only supported by `omni-moderation-latest`.
+    Parts(Vec<ModerationInputPart>),
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ModerationInputPart {
+    Text { text: String },
+    ImageUrl { image_url: ModerationImageUrl },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ModerationImageUrl {
+    /// A URL of the image, or its base64 encoded data as a data URL.
+    pub url: String,
+}
+
+impl ModerationInputPart {
+    pub fn text(text: impl Into<String>) -> Self {
+        ModerationInputPart::Text { text: text.into() }
+    }
+
+    pub fn image_url(url: impl Into<String>) -> Self {
+        ModerationInputPart::ImageUrl {
+            image_url: ModerationImageUrl { url: url.into() },
+        }
+    }
+}
+
+impl From<String> for ModerationInput {
+    fn from(text: String) -> Self {
+        ModerationInput::Text(text)
+    }
+}
+
+impl From<&str> for ModerationInput {
+    fn from(text: &str) -> Self {
+        ModerationInput::Text(text.to_string())
+    }
+}
+
+impl From<Vec<String>> for ModerationInput {
+    fn from(texts: Vec<String>) -> Self {
+        ModerationInput::Texts(texts)
+    }
+}
+
+impl From<Vec<&str>> for ModerationInput {
+    fn from(texts: Vec<&str>) -> Self {
+        ModerationInput::Texts(texts.into_iter().map(String::from).collect())
+    }
+}
+
+impl From<Vec<ModerationInputPart>> for ModerationInput {
+    fn from(parts: Vec<ModerationInputPart>) -> Self {
+        ModerationInput::Parts(parts)
+    }
+}
+
 impl Moderation {
     async fn create(request: ModerationRequest) -> ApiResponseOrError<Self> {
-        openai_post("moderations", &request, request.credentials.clone()).await
+        let credentials_opt = request.credentials.clone();
+        openai_post("moderations", &request, credentials_opt).await
     }
 
-    pub fn builder(input: impl Into<String>) -> ModerationBuilder {
+    pub fn builder(input: impl Into<ModerationInput>) -> ModerationBuilder {
         ModerationBuilder::create_empty().input(input)
     }
 }
@@ -104,4 +196,130 @@ mod tests {
         );
         assert_eq!(moderation.results.first().unwrap().flagged, true);
     }
+
+    #[test]
+    fn default_model() {
+        let request = Moderation::builder("I want to kill them.").build().unwrap();
+
+        assert!(request.credentials.is_none());
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "input": "I want to kill them.",
+                "model": "omni-moderation-latest",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn multiple_inputs() {
+        let result = |flagged: bool, violence: f64| {
+            serde_json::json!({
+                "flagged": flagged,
+                "categories": {
+                    "hate": false,
+                    "hate/threatening": false,
+                    "self-harm": false,
+                    "sexual": false,
+                    "sexual/minors": false,
+                    "violence": flagged,
+                    "violence/graphic": false,
+                },
+                "category_scores": {
+                    "hate": 0.0,
+                    "hate/threatening": 0.0,
+                    "self-harm": 0.0,
+                    "sexual": 0.0,
+                    "sexual/minors": 0.0,
+                    "violence": violence,
+                    "violence/graphic": 0.0,
+                },
+            })
+        };
+        let (credentials, server) = crate::tests::mock_server(
+            200,
+            &serde_json::json!({
+                "id": "modr-123",
+                "model": "omni-moderation-latest",
+                "results": [result(false, 0.001), result(true, 0.97)],
+            })
+            .to_string(),
+        )
+        .await;
+
+        let moderation = Moderation::builder(vec!["I love crabs.", "I want to kill them."])
+            .credentials(credentials)
+            .create()
+            .await
+            .unwrap();
+
+        assert_eq!(moderation.results.len(), 2);
+        assert!(!moderation.results[0].flagged);
+        assert!(moderation.results[1].categories.violence);
+        let request = server.await.unwrap();
+        assert!(request.contains(r#""input":["I love crabs.","I want to kill them."]"#));
+    }
+
+    #[test]
+    fn current_categories() {
+        let result: ModerationResult = serde_json::from_value(serde_json::json!({
+            "flagged": true,
+            "categories": {
+                "harassment": true,
+                "harassment/threatening": true,
+                "hate": false,
+                "hate/threatening": false,
+                "illicit": false,
+                "illicit/violent": true,
+                "self-harm": false,
+                "self-harm/intent": false,
+                "self-harm/instructions": false,
+                "sexual": false,
+                "sexual/minors": false,
+                "violence": true,
+                "violence/graphic": false,
+            },
+            "category_scores": {
+                "harassment": 0.8189,
+                "harassment/threatening": 0.8046,
+                "hate": 0.0013,
+                "hate/threatening": 0.0004,
+                "illicit": 0.0312,
+                "illicit/violent": 0.5124,
+                "self-harm": 0.0001,
+                "self-harm/intent": 0.0002,
+                "self-harm/instructions": 0.0001,
+                "sexual": 0.0001,
+                "sexual/minors": 0.0,
+                "violence": 0.9972,
+                "violence/graphic": 0.0043,
+            },
+        }))
+        .unwrap();
+
+        assert!(result.categories.harassment_threatening);
+        assert!(result.categories.illicit_violent);
+        assert!(!result.categories.self_harm_intent);
+        assert_eq!(result.category_scores.harassment, Some(0.8189));
+        assert_eq!(result.category_scores.illicit_violent, Some(0.5124));
+        assert_eq!(result.category_scores.self_harm_instructions, Some(0.0001));
+    }
+
+    #[test]
+    fn image_input() {
+        let request = Moderation::builder(vec![
+            ModerationInputPart::text("Is this fine?"),
+            ModerationInputPart::image_url("https://example.com/crab.png"),
+        ])
+        .build()
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["input"],
+            serde_json::json!([
+                { "type": "text", "text": "Is this fine?" },
+                { "type": "image_url", "image_url": { "url": "https://example.com/crab.png" } },
+            ])
+        );
+    }
 }
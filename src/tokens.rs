@@ -0,0 +1,56 @@
+//! Count the tokens in a piece of text, to budget prompts against a model's context length.
+//!
+//! With the `tiktoken` feature, tokens are counted with the BPE encoding of the model's family.
+//! Without it, they are estimated as one token per four characters,
+//! which is cheap but can be off several-fold for non-English text, code and emoji.
+
+/// Counts the tokens `text` encodes to for `model`.
+///
+/// Models without a known encoding, such as those from other providers,
+/// are counted with the encoding of the newest OpenAI models.
+#[cfg(feature = "tiktoken")]
+pub fn count_tokens(text: &str, model: &str) -> usize {
+    use tiktoken_rs::tokenizer::Tokenizer;
+
+    let bpe = match tiktoken_rs::tokenizer::get_tokenizer(model) {
+        Some(Tokenizer::Cl100kBase) => tiktoken_rs::cl100k_base_singleton(),
+        Some(Tokenizer::P50kBase) => tiktoken_rs::p50k_base_singleton(),
+        Some(Tokenizer::P50kEdit) => tiktoken_rs::p50k_edit_singleton(),
+        Some(Tokenizer::R50kBase | Tokenizer::Gpt2) => tiktoken_rs::r50k_base_singleton(),
+        Some(Tokenizer::O200kBase) | None => tiktoken_rs::o200k_base_singleton(),
+    };
+    bpe.encode_with_special_tokens(text).len()
+}
+
+/// Estimates the tokens `text` encodes to as one token per four characters.
+/// Enable the `tiktoken` feature to count them exactly.
+#[cfg(not(feature = "tiktoken"))]
+pub fn count_tokens(text: &str, _model: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_text() {
+        assert_eq!(count_tokens("", "gpt-4o"), 0);
+    }
+
+    #[cfg(not(feature = "tiktoken"))]
+    #[test]
+    fn estimated_tokens() {
+        assert_eq!(count_tokens("hello world", "gpt-4o"), 3);
+        assert_eq!(count_tokens("こんにちは", "gpt-4o"), 2);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn counted_tokens() {
+        assert_eq!(count_tokens("hello world", "gpt-4o"), 2);
+        assert_eq!(count_tokens("hello world", "gpt-3.5-turbo"), 2);
+        assert_eq!(count_tokens("hello world", "llama-3.1-8b"), 2);
+        assert!(count_tokens("fn main() { println!(\"🦀\"); }", "gpt-4") > 4);
+    }
+}